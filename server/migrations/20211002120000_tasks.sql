@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS tasks (
+    id SERIAL PRIMARY KEY,
+    user_id INT NOT NULL REFERENCES users (id),
+    article_id INT NOT NULL REFERENCES articles (id) ON DELETE CASCADE,
+
+    line INT NOT NULL,
+    text TEXT NOT NULL,
+    checked BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE INDEX tasks_article ON tasks (article_id);
+CREATE INDEX tasks_open ON tasks (user_id) WHERE NOT checked;
@@ -0,0 +1,15 @@
+CREATE TABLE IF NOT EXISTS notebooks (
+    id SERIAL PRIMARY KEY,
+    user_id INT NOT NULL REFERENCES users (id),
+    parent_id INT REFERENCES notebooks (id),
+
+    name TEXT NOT NULL,
+
+    created_on TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX notebooks_parent ON notebooks (parent_id);
+
+ALTER TABLE articles ADD COLUMN notebook_id INT REFERENCES notebooks (id) ON DELETE SET NULL;
+
+CREATE INDEX articles_notebook ON articles (notebook_id);
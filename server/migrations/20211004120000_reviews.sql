@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS reviews (
+    article_id INT PRIMARY KEY REFERENCES articles (id) ON DELETE CASCADE,
+    user_id INT NOT NULL REFERENCES users (id),
+
+    repetitions INT NOT NULL DEFAULT 0,
+    interval_days INT NOT NULL DEFAULT 0,
+    ease DOUBLE PRECISION NOT NULL DEFAULT 2.5,
+
+    due_on DATE NOT NULL DEFAULT CURRENT_DATE,
+    reviewed_on DATE
+);
+
+CREATE INDEX reviews_due ON reviews (user_id, due_on);
@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS daily_notes (
+    user_id INT NOT NULL REFERENCES users (id),
+    day DATE NOT NULL,
+    article_id INT NOT NULL REFERENCES articles (id) ON DELETE CASCADE,
+    CONSTRAINT daily_notes_pkey PRIMARY KEY (user_id, day)
+);
@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS article_revisions (
+    id SERIAL PRIMARY KEY,
+    article_id INT NOT NULL REFERENCES articles (id) ON DELETE CASCADE,
+
+    title TEXT NOT NULL,
+    content TEXT NOT NULL,
+    tags TEXT[] NOT NULL,
+
+    created_on TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX article_revisions_article ON article_revisions (article_id);
@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS saved_searches (
+    id SERIAL PRIMARY KEY,
+    user_id INT NOT NULL REFERENCES users (id),
+
+    name TEXT NOT NULL,
+    query TEXT NOT NULL DEFAULT '',
+    tags INT[] NOT NULL DEFAULT '{}',
+    all_tags BOOLEAN NOT NULL DEFAULT FALSE,
+    sort_by_created BOOLEAN NOT NULL DEFAULT FALSE,
+    notebook_id INT REFERENCES notebooks (id) ON DELETE SET NULL,
+
+    created_on TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
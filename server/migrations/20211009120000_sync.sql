@@ -0,0 +1,69 @@
+CREATE SEQUENCE IF NOT EXISTS change_seq;
+
+ALTER TABLE articles ADD COLUMN seq BIGINT NOT NULL DEFAULT nextval('change_seq');
+ALTER TABLE tags ADD COLUMN seq BIGINT NOT NULL DEFAULT nextval('change_seq');
+ALTER TABLE links ADD COLUMN seq BIGINT NOT NULL DEFAULT nextval('change_seq');
+
+CREATE INDEX articles_seq ON articles (user_id, seq);
+CREATE INDEX tags_seq ON tags (user_id, seq);
+CREATE INDEX links_seq ON links (user_id, seq);
+
+-- Trigger for seq
+
+CREATE OR REPLACE FUNCTION trigger_change_seq()
+RETURNS TRIGGER AS $$
+BEGIN
+  NEW.seq = nextval('change_seq');
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER articles_change_seq
+BEFORE UPDATE ON articles
+FOR EACH ROW
+EXECUTE FUNCTION trigger_change_seq();
+
+CREATE TRIGGER tags_change_seq
+BEFORE UPDATE ON tags
+FOR EACH ROW
+EXECUTE FUNCTION trigger_change_seq();
+
+CREATE TRIGGER links_change_seq
+BEFORE UPDATE ON links
+FOR EACH ROW
+EXECUTE FUNCTION trigger_change_seq();
+
+-- Tombstones for deleted records
+
+CREATE TABLE IF NOT EXISTS tombstones (
+    seq BIGINT PRIMARY KEY DEFAULT nextval('change_seq'),
+    user_id INT NOT NULL,
+    kind TEXT NOT NULL,
+    record_id INT NOT NULL,
+    deleted_on TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX tombstones_user_seq ON tombstones (user_id, seq);
+
+CREATE OR REPLACE FUNCTION trigger_tombstone()
+RETURNS TRIGGER AS $$
+BEGIN
+  INSERT INTO tombstones (user_id, kind, record_id) VALUES (OLD.user_id, TG_ARGV[0], OLD.id);
+  RETURN OLD;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER articles_tombstone
+AFTER DELETE ON articles
+FOR EACH ROW
+EXECUTE FUNCTION trigger_tombstone('article');
+
+CREATE TRIGGER tags_tombstone
+AFTER DELETE ON tags
+FOR EACH ROW
+EXECUTE FUNCTION trigger_tombstone('tag');
+
+CREATE TRIGGER links_tombstone
+AFTER DELETE ON links
+FOR EACH ROW
+EXECUTE FUNCTION trigger_tombstone('link');
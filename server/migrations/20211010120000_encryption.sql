@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS user_keys (
+    user_id INT PRIMARY KEY REFERENCES users (id) ON DELETE CASCADE,
+    -- data key encrypted with the server master key
+    wrapped_key TEXT NOT NULL,
+
+    created_on TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- raw_text may be encrypted, the vector is built from plaintext on write
+ALTER TABLE articles ALTER COLUMN search_vector DROP EXPRESSION;
@@ -0,0 +1,6 @@
+-- namespaces of existing tags become tags
+INSERT INTO tags (user_id, name)
+SELECT DISTINCT t.user_id, ARRAY_TO_STRING((STRING_TO_ARRAY(t.name, '/'))[1:n], '/')
+FROM tags t,
+    GENERATE_SERIES(1, CARDINALITY(STRING_TO_ARRAY(t.name, '/')) - 1) n
+ON CONFLICT DO NOTHING;
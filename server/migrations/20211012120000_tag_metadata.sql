@@ -0,0 +1,4 @@
+ALTER TABLE tags
+    ADD COLUMN color TEXT,
+    ADD COLUMN description TEXT,
+    ADD COLUMN icon TEXT;
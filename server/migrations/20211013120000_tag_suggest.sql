@@ -0,0 +1,6 @@
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+-- prefix matches
+CREATE INDEX tags_name_prefix ON tags (user_id, name text_pattern_ops);
+-- fuzzy matches
+CREATE INDEX tags_name_trgm ON tags USING GIN (name gin_trgm_ops);
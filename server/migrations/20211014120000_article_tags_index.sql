@@ -0,0 +1,2 @@
+-- mapping lookups by tag, e.g. orphan cleanup and merges
+CREATE INDEX article_tags_tag ON article_tags (tag_id);
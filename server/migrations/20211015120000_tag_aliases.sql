@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS tag_aliases (
+    id SERIAL PRIMARY KEY,
+    user_id INT NOT NULL REFERENCES users (id),
+    tag_id INT NOT NULL REFERENCES tags (id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    UNIQUE (user_id, name)
+);
+
+CREATE INDEX tag_aliases_tag ON tag_aliases (tag_id);
@@ -0,0 +1,2 @@
+-- notes are claimed before their article is created
+ALTER TABLE daily_notes ALTER COLUMN article_id DROP NOT NULL;
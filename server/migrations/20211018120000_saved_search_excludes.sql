@@ -0,0 +1,24 @@
+ALTER TABLE saved_searches ADD COLUMN exclude_tags INT[] NOT NULL DEFAULT '{}';
+
+-- drop ids of already deleted tags
+
+UPDATE saved_searches s SET tags = ARRAY(
+    SELECT f.id FROM UNNEST(s.tags) f (id) WHERE EXISTS(SELECT 1 FROM tags WHERE id = f.id)
+);
+
+-- Trigger removing deleted tags from saved searches
+
+CREATE OR REPLACE FUNCTION trigger_saved_search_tags()
+RETURNS TRIGGER AS $$
+BEGIN
+  UPDATE saved_searches
+  SET tags = ARRAY_REMOVE(tags, OLD.id), exclude_tags = ARRAY_REMOVE(exclude_tags, OLD.id)
+  WHERE user_id = OLD.user_id AND (OLD.id = ANY(tags) OR OLD.id = ANY(exclude_tags));
+  RETURN OLD;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER tags_saved_searches
+AFTER DELETE ON tags
+FOR EACH ROW
+EXECUTE FUNCTION trigger_saved_search_tags();
@@ -0,0 +1,6 @@
+-- pending deliveries are dispatched from the table, NULL when done or out of attempts
+ALTER TABLE webhook_deliveries ADD COLUMN next_attempt_on TIMESTAMP;
+ALTER TABLE webhook_deliveries ALTER COLUMN next_attempt_on SET DEFAULT CURRENT_TIMESTAMP;
+
+CREATE INDEX webhook_deliveries_pending ON webhook_deliveries (next_attempt_on)
+    WHERE next_attempt_on IS NOT NULL;
@@ -0,0 +1,21 @@
+-- Writing transaction of each change. Sync cursors are snapshot xmins,
+-- transactions below are finished, so no change can commit behind a cursor.
+
+ALTER TABLE articles ADD COLUMN txid BIGINT NOT NULL DEFAULT txid_current();
+ALTER TABLE tags ADD COLUMN txid BIGINT NOT NULL DEFAULT txid_current();
+ALTER TABLE links ADD COLUMN txid BIGINT NOT NULL DEFAULT txid_current();
+ALTER TABLE tombstones ADD COLUMN txid BIGINT NOT NULL DEFAULT txid_current();
+
+CREATE INDEX articles_txid ON articles (user_id, txid);
+CREATE INDEX tags_txid ON tags (user_id, txid);
+CREATE INDEX links_txid ON links (user_id, txid);
+CREATE INDEX tombstones_user_txid ON tombstones (user_id, txid);
+
+CREATE OR REPLACE FUNCTION trigger_change_seq()
+RETURNS TRIGGER AS $$
+BEGIN
+  NEW.seq = nextval('change_seq');
+  NEW.txid = txid_current();
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
@@ -1,6 +1,7 @@
 use rocket::serde::json::Json;
-use super::{ApiErr, AsHttpStatus, Db, LinkAnalyzer, User};
+use super::{ApiErr, AsHttpStatus, Db, Events, LinkAnalyzer, User};
 use crate::db::article;
+use crate::db::tags::ScoredTag;
 
 // ========================== TYPES =======================
 
@@ -14,7 +15,10 @@ impl AsHttpStatus for article::ArticleError {
         use rocket::http::Status;
         match &self {
             ArticleError::BadContent => Status::BadRequest,
+            ArticleError::Invalid(_) => Status::BadRequest,
+            ArticleError::UnknownTags(_) => Status::BadRequest,
             ArticleError::NotFound => Status::NotFound,
+            ArticleError::Conflict => Status::Conflict,
             ArticleError::Internal(_) => Status::InternalServerError,
         }
     }
@@ -27,25 +31,37 @@ pub async fn get(db: &Db, id: i32, user: User) -> ApiResult<article::Article> {
     Ok(Json(article::get(db.as_ref(), user.id, id).await?))
 }
 
-#[get("/article?<from>&<limit>&<tags>&<sort_by>&<all_tags>&<query>")]
+#[get("/article/<id>/suggested-tags")]
+pub async fn suggested_tags(db: &Db, id: i32, user: User) -> ApiResult<Vec<ScoredTag>> {
+    Ok(Json(article::suggested_tags(db.as_ref(), user.id, id).await?))
+}
+
+#[get("/article?<from>&<limit>&<tags>&<exclude_tags>&<sort_by>&<all_tags>&<query>&<notebook>")]
 pub async fn list(
     db: &Db,
     user: User,
     from: Option<u32>,
     limit: Option<u32>,
-    tags: Option<String>,
+    tags: Option<&'_ str>,
+    exclude_tags: Option<&'_ str>,
     all_tags: Option<bool>,
     sort_by: Option<&'_ str>,
     query: Option<String>,
+    notebook: Option<i32>,
 ) -> ApiResult<Vec<article::ArticlePreview>> {
-    let tags = tags.map(|s| s.split(',').map(|s| s.parse()).flatten().collect());
+    // tags are given by name or id
+    let tags = article::resolve_tags(db.as_ref(), user.id, &split_tags(tags)).await?;
+    let exclude_tags =
+        article::resolve_tags(db.as_ref(), user.id, &split_tags(exclude_tags)).await?;
     let options = article::ListOptions {
         offset: from.unwrap_or(0),
         limit: limit.unwrap_or(10),
-        tags: tags.unwrap_or(vec![]),
+        tags,
         all_tags: all_tags.unwrap_or(false),
+        exclude_tags,
         sort_by_created: sort_by.map(|s| s == "created").unwrap_or(false),
         query: query.unwrap_or_default(),
+        notebook,
     };
     Ok(Json(article::list(db.as_ref(), user.id, options).await?))
 }
@@ -54,20 +70,43 @@ pub async fn list(
 pub async fn update(
     db: &Db,
     link_analyzer: &LinkAnalyzer,
+    events: &Events,
     user: User,
     article: Json<article::ArticleInsert<'_>>,
 ) -> ApiResult<i32> {
     let id = if let Some(id) = article.id {
-        article::update(db.as_ref(), link_analyzer.as_ref(), user.id, article.0).await?;
+        article::update(
+            db.as_ref(),
+            link_analyzer.as_ref(),
+            events.as_ref(),
+            user.id,
+            article.0,
+            None,
+        )
+        .await?;
         id
     } else {
-        article::create(db.as_ref(), link_analyzer.as_ref(), user.id, article.0).await?
+        article::create(
+            db.as_ref(),
+            link_analyzer.as_ref(),
+            events.as_ref(),
+            user.id,
+            article.0,
+        )
+        .await?
     };
     Ok(Json(id))
 }
 
 #[delete("/article/<id>")]
-pub async fn delete(db: &Db, user: User, id: i32) -> ApiResult<()> {
-    article::delete(db.as_ref(), user.id, id).await?;
+pub async fn delete(db: &Db, events: &Events, user: User, id: i32) -> ApiResult<()> {
+    article::delete(db.as_ref(), events.as_ref(), user.id, id, None).await?;
     Ok(Json(()))
 }
+
+// ========================== HELPERS =====================
+
+pub fn split_tags(tags: Option<&str>) -> Vec<&str> {
+    tags.map(|s| s.split(',').map(str::trim).filter(|t| !t.is_empty()).collect())
+        .unwrap_or_default()
+}
@@ -0,0 +1,87 @@
+use chrono::{Datelike, Duration, Local, NaiveDate};
+use rocket::serde::json::Json;
+use super::{ApiErr, AsHttpStatus, DailyConfig, Db, Events, LinkAnalyzer, User};
+use crate::db::{article, daily};
+
+// ========================== TYPES =======================
+
+type ApiResult<T> = Result<Json<T>, ApiErr<daily::DailyError>>;
+
+// ========================== ERRORS ======================
+
+impl AsHttpStatus for daily::DailyError {
+    fn status(&self) -> rocket::http::Status {
+        use daily::DailyError;
+        use rocket::http::Status;
+        match &self {
+            DailyError::BadDate => Status::BadRequest,
+            DailyError::Article(e) => e.status(),
+            DailyError::Internal(_) => Status::InternalServerError,
+        }
+    }
+}
+
+// ========================= RESPONDERS ===================
+
+#[get("/daily/<date>")]
+pub async fn get(
+    db: &Db,
+    link_analyzer: &LinkAnalyzer,
+    events: &Events,
+    config: &DailyConfig,
+    user: User,
+    date: &str,
+) -> ApiResult<article::Article> {
+    let day = if date == "today" {
+        today()
+    } else {
+        parse_date(date)?
+    };
+    Ok(Json(
+        daily::get_or_create(
+            db.as_ref(),
+            link_analyzer.as_ref(),
+            events.as_ref(),
+            config.as_ref(),
+            user.id,
+            day,
+        )
+        .await?,
+    ))
+}
+
+/// Lists days with entries, defaults to the current month
+#[get("/daily?<from>&<to>")]
+pub async fn calendar(
+    db: &Db,
+    user: User,
+    from: Option<&str>,
+    to: Option<&str>,
+) -> ApiResult<Vec<daily::DailyEntry>> {
+    let month_start = today().with_day(1).expect("First day of month");
+    let from = from.map(parse_date).transpose()?.unwrap_or(month_start);
+    let to = match to {
+        Some(to) => parse_date(to)?,
+        None => next_month(from) - Duration::days(1),
+    };
+    Ok(Json(daily::calendar(db.as_ref(), user.id, (from, to)).await?))
+}
+
+// ========================== HELPERS =====================
+
+fn today() -> NaiveDate {
+    Local::now().naive_local().date()
+}
+
+fn next_month(day: NaiveDate) -> NaiveDate {
+    let (year, month) = if day.month() == 12 {
+        (day.year() + 1, 1)
+    } else {
+        (day.year(), day.month() + 1)
+    };
+    NaiveDate::from_ymd_opt(year, month, 1).expect("First day of the month exists")
+}
+
+fn parse_date(date: &str) -> Result<NaiveDate, daily::DailyError> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| daily::DailyError::BadDate)
+}
@@ -0,0 +1,32 @@
+use rocket::response::stream::{Event, EventStream};
+use rocket::tokio::{select, sync::broadcast::error::RecvError};
+use rocket::Shutdown;
+use super::{Events, User};
+
+// ========================= RESPONDERS ===================
+
+/// Streams change events of the user.
+/// A `resync` event is sent when events were dropped and clients should reload.
+#[get("/events")]
+pub async fn stream(events: &Events, user: User, mut shutdown: Shutdown) -> EventStream![] {
+    let mut recv = events.subscribe();
+    EventStream! {
+        loop {
+            let event = select! {
+                event = recv.recv() => match event {
+                    Ok(event) => event,
+                    Err(RecvError::Closed) => break,
+                    Err(RecvError::Lagged(_)) => {
+                        yield Event::data("").event("resync");
+                        continue;
+                    }
+                },
+                _ = &mut shutdown => break,
+            };
+            if event.user != user.id {
+                continue;
+            }
+            yield Event::json(&event.kind).event(event.kind.name());
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use std::sync;
+use std::sync::Arc;
 
 use rocket::http::Status;
 use rocket::request::{FromRequest, Outcome};
@@ -8,15 +8,29 @@ use rocket::{Request, Response, State};
 use crate::security;
 
 pub mod article;
+pub mod daily;
+pub mod events;
 pub mod links;
+pub mod notebooks;
+pub mod review;
+pub mod revisions;
+pub mod searches;
+pub mod sync;
 pub mod tags;
+pub mod tasks;
+pub mod templates;
 pub mod user;
+pub mod versions;
+pub mod webhooks;
 
 // ========================= TYPES ========================
 
-type Db = State<sync::Arc<crate::db::Db>>;
-type LinkAnalyzer = State<sync::Arc<crate::db::links::Analyzer>>;
-type SecurityConfig = State<sync::Arc<security::Config>>;
+type Db = State<Arc<crate::db::Db>>;
+type LinkAnalyzer = State<Arc<crate::db::links::Analyzer>>;
+type Events = State<Arc<crate::db::events::Events>>;
+type SecurityConfig = State<Arc<security::Config>>;
+type DailyConfig = State<Arc<crate::db::daily::Config>>;
+type VersionsConfig = State<Arc<crate::db::versions::Config>>;
 pub struct User {
     pub id: i32,
 }
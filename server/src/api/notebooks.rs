@@ -0,0 +1,82 @@
+use rocket::serde::{json::Json, Deserialize};
+use super::{ApiErr, AsHttpStatus, Db, User};
+use crate::db::notebooks;
+
+// ========================== TYPES =======================
+
+type ApiResult<T> = Result<Json<T>, ApiErr<notebooks::NotebookError>>;
+
+#[derive(Deserialize)]
+pub struct RenameInfo<'r> {
+    pub name: &'r str,
+}
+
+#[derive(Deserialize)]
+pub struct MoveInfo {
+    pub parent_id: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct ArticleMoveInfo {
+    pub notebook_id: Option<i32>,
+}
+
+// ========================== ERRORS ======================
+
+impl AsHttpStatus for notebooks::NotebookError {
+    fn status(&self) -> rocket::http::Status {
+        use notebooks::NotebookError;
+        use rocket::http::Status;
+        match &self {
+            NotebookError::NotFound => Status::NotFound,
+            NotebookError::BadContent => Status::BadRequest,
+            NotebookError::BadParent => Status::BadRequest,
+            NotebookError::Internal(_) => Status::InternalServerError,
+        }
+    }
+}
+
+// ========================= RESPONDERS ===================
+
+#[get("/notebooks")]
+pub async fn list(db: &Db, user: User) -> ApiResult<Vec<notebooks::Notebook>> {
+    Ok(Json(notebooks::list(db.as_ref(), user.id).await?))
+}
+
+#[post("/notebooks", data = "<notebook>")]
+pub async fn create(
+    db: &Db,
+    user: User,
+    notebook: Json<notebooks::NotebookInsert<'_>>,
+) -> ApiResult<i32> {
+    Ok(Json(notebooks::create(db.as_ref(), user.id, notebook.0).await?))
+}
+
+#[patch("/notebooks/<id>", data = "<info>")]
+pub async fn rename(db: &Db, user: User, id: i32, info: Json<RenameInfo<'_>>) -> ApiResult<()> {
+    notebooks::rename(db.as_ref(), user.id, id, info.name).await?;
+    Ok(Json(()))
+}
+
+#[post("/notebooks/<id>/move", data = "<info>")]
+pub async fn move_notebook(db: &Db, user: User, id: i32, info: Json<MoveInfo>) -> ApiResult<()> {
+    notebooks::move_notebook(db.as_ref(), user.id, id, info.parent_id).await?;
+    Ok(Json(()))
+}
+
+#[delete("/notebooks/<id>")]
+pub async fn delete(db: &Db, user: User, id: i32) -> ApiResult<()> {
+    notebooks::delete(db.as_ref(), user.id, id).await?;
+    Ok(Json(()))
+}
+
+#[post("/article/<id>/move", data = "<info>")]
+pub async fn move_article(
+    db: &Db,
+    user: User,
+    id: i32,
+    info: Json<ArticleMoveInfo>,
+) -> ApiResult<()> {
+    notebooks::move_article(db.as_ref(), user.id, id, info.notebook_id).await?;
+    Ok(Json(()))
+}
@@ -0,0 +1,49 @@
+use rocket::serde::{json::Json, Deserialize};
+use super::{ApiErr, AsHttpStatus, Db, User};
+use crate::db::{article::ArticlePreview, review};
+
+// ========================== TYPES =======================
+
+type ApiResult<T> = Result<Json<T>, ApiErr<review::ReviewError>>;
+
+#[derive(Deserialize)]
+pub struct GradeInfo {
+    pub quality: i32,
+}
+
+// ========================== ERRORS ======================
+
+impl AsHttpStatus for review::ReviewError {
+    fn status(&self) -> rocket::http::Status {
+        use review::ReviewError;
+        use rocket::http::Status;
+        match &self {
+            ReviewError::NotFound => Status::NotFound,
+            ReviewError::BadGrade => Status::BadRequest,
+            ReviewError::Internal(_) => Status::InternalServerError,
+        }
+    }
+}
+
+// ========================= RESPONDERS ===================
+
+#[post("/article/<id>/review")]
+pub async fn enable(db: &Db, user: User, id: i32) -> ApiResult<review::Review> {
+    Ok(Json(review::enable(db.as_ref(), user.id, id).await?))
+}
+
+#[delete("/article/<id>/review")]
+pub async fn disable(db: &Db, user: User, id: i32) -> ApiResult<()> {
+    review::disable(db.as_ref(), user.id, id).await?;
+    Ok(Json(()))
+}
+
+#[get("/review/due?<limit>")]
+pub async fn due(db: &Db, user: User, limit: Option<u32>) -> ApiResult<Vec<ArticlePreview>> {
+    Ok(Json(review::due(db.as_ref(), user.id, limit.unwrap_or(50)).await?))
+}
+
+#[post("/review/<id>/grade", data = "<info>")]
+pub async fn grade(db: &Db, user: User, id: i32, info: Json<GradeInfo>) -> ApiResult<review::Review> {
+    Ok(Json(review::grade(db.as_ref(), user.id, id, info.quality).await?))
+}
@@ -0,0 +1,39 @@
+use rocket::serde::json::Json;
+use super::{ApiErr, AsHttpStatus, Db, User};
+use crate::db::revisions;
+
+// ========================== TYPES =======================
+
+type ApiResult<T> = Result<Json<T>, ApiErr<revisions::RevisionError>>;
+
+// ========================== ERRORS ======================
+
+impl AsHttpStatus for revisions::RevisionError {
+    fn status(&self) -> rocket::http::Status {
+        use revisions::RevisionError;
+        use rocket::http::Status;
+        match &self {
+            RevisionError::RevisionNotFound => Status::NotFound,
+            RevisionError::Article(e) => e.status(),
+            RevisionError::Internal(_) => Status::InternalServerError,
+        }
+    }
+}
+
+// ========================= RESPONDERS ===================
+
+#[get("/article/<id>/revisions")]
+pub async fn list(db: &Db, user: User, id: i32) -> ApiResult<Vec<revisions::Revision>> {
+    Ok(Json(revisions::list(db.as_ref(), user.id, id).await?))
+}
+
+#[get("/article/<id>/diff?<from>&<to>")]
+pub async fn diff(
+    db: &Db,
+    user: User,
+    id: i32,
+    from: i32,
+    to: Option<i32>,
+) -> ApiResult<revisions::Diff> {
+    Ok(Json(revisions::diff(db.as_ref(), user.id, id, from, to).await?))
+}
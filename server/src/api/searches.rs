@@ -0,0 +1,67 @@
+use rocket::serde::json::Json;
+use super::{ApiErr, AsHttpStatus, Db, User};
+use crate::db::{article::ArticlePreview, searches};
+
+// ========================== TYPES =======================
+
+type ApiResult<T> = Result<Json<T>, ApiErr<searches::SearchError>>;
+
+// ========================== ERRORS ======================
+
+impl AsHttpStatus for searches::SearchError {
+    fn status(&self) -> rocket::http::Status {
+        use rocket::http::Status;
+        use searches::SearchError;
+        match &self {
+            SearchError::NotFound => Status::NotFound,
+            SearchError::BadContent => Status::BadRequest,
+            SearchError::Article(e) => e.status(),
+            SearchError::Internal(_) => Status::InternalServerError,
+        }
+    }
+}
+
+// ========================= RESPONDERS ===================
+
+#[get("/saved-searches")]
+pub async fn list(db: &Db, user: User) -> ApiResult<Vec<searches::SavedSearchCount>> {
+    Ok(Json(searches::list(db.as_ref(), user.id).await?))
+}
+
+#[get("/saved-searches/<id>")]
+pub async fn get(db: &Db, user: User, id: i32) -> ApiResult<searches::SavedSearch> {
+    Ok(Json(searches::get(db.as_ref(), user.id, id).await?))
+}
+
+#[get("/saved-searches/<id>/articles?<from>&<limit>")]
+pub async fn articles(
+    db: &Db,
+    user: User,
+    id: i32,
+    from: Option<u32>,
+    limit: Option<u32>,
+) -> ApiResult<Vec<ArticlePreview>> {
+    let page = (from.unwrap_or(0), limit.unwrap_or(10));
+    Ok(Json(searches::articles(db.as_ref(), user.id, id, page).await?))
+}
+
+#[post("/saved-searches", data = "<search>")]
+pub async fn update(
+    db: &Db,
+    user: User,
+    search: Json<searches::SavedSearchInsert<'_>>,
+) -> ApiResult<i32> {
+    let id = if let Some(id) = search.id {
+        searches::update(db.as_ref(), user.id, search.0).await?;
+        id
+    } else {
+        searches::create(db.as_ref(), user.id, search.0).await?
+    };
+    Ok(Json(id))
+}
+
+#[delete("/saved-searches/<id>")]
+pub async fn delete(db: &Db, user: User, id: i32) -> ApiResult<()> {
+    searches::delete(db.as_ref(), user.id, id).await?;
+    Ok(Json(()))
+}
@@ -0,0 +1,47 @@
+use rocket::serde::json::Json;
+use super::{ApiErr, AsHttpStatus, Db, Events, LinkAnalyzer, User};
+use crate::db::sync;
+
+// ========================== TYPES =======================
+
+type ApiResult<T> = Result<Json<T>, ApiErr<sync::SyncError>>;
+
+// ========================== ERRORS ======================
+
+impl AsHttpStatus for sync::SyncError {
+    fn status(&self) -> rocket::http::Status {
+        use rocket::http::Status;
+        use sync::SyncError;
+        match &self {
+            SyncError::Internal(_) => Status::InternalServerError,
+        }
+    }
+}
+
+// ========================= RESPONDERS ===================
+
+/// `since` is the `cursor` of the previous sync, all records are sent without it
+#[get("/sync?<since>")]
+pub async fn changes(db: &Db, user: User, since: Option<i64>) -> ApiResult<sync::Changes> {
+    Ok(Json(sync::changes(db.as_ref(), user.id, since.unwrap_or(0)).await?))
+}
+
+#[post("/sync", data = "<changes>")]
+pub async fn push(
+    db: &Db,
+    link_analyzer: &LinkAnalyzer,
+    events: &Events,
+    user: User,
+    changes: Json<Vec<sync::PushChange>>,
+) -> ApiResult<Vec<sync::PushResult>> {
+    Ok(Json(
+        sync::push(
+            db.as_ref(),
+            link_analyzer.as_ref(),
+            events.as_ref(),
+            user.id,
+            changes.0,
+        )
+        .await?,
+    ))
+}
@@ -1,11 +1,28 @@
-use rocket::serde::json::Json;
-use super::{ApiErr, AsHttpStatus, Db, User};
+use rocket::http::ContentType;
+use rocket::serde::{json::Json, Deserialize};
+use super::{ApiErr, AsHttpStatus, Db, Events, User};
 use crate::db::tags;
 
 // ========================== TYPES =======================
 
 type ApiResult<T> = Result<Json<T>, ApiErr<tags::TagError>>;
 
+#[derive(Responder)]
+pub enum GraphResponse {
+    Json(Json<tags::TagGraph>),
+    GraphMl(String, ContentType),
+}
+
+#[derive(Deserialize)]
+pub struct AliasInfo<'r> {
+    pub name: &'r str,
+}
+
+#[derive(Deserialize)]
+pub struct MergeInfo {
+    pub into: i32,
+}
+
 // ========================== ERRORS ======================
 
 impl AsHttpStatus for tags::TagError {
@@ -13,6 +30,12 @@ impl AsHttpStatus for tags::TagError {
         use rocket::http::Status;
         use tags::TagError;
         match &self {
+            TagError::NotFound => Status::NotFound,
+            TagError::Invalid(_) => Status::BadRequest,
+            TagError::Conflict => Status::Conflict,
+            TagError::SameTag => Status::BadRequest,
+            TagError::UnknownFormat => Status::BadRequest,
+            TagError::UnknownTags(_) => Status::BadRequest,
             TagError::Internal(_) => Status::InternalServerError,
         }
     }
@@ -24,3 +47,96 @@ impl AsHttpStatus for tags::TagError {
 pub async fn list(db: &Db, user: User) -> ApiResult<Vec<tags::Tag>> {
     Ok(Json(tags::list(db.as_ref(), user.id).await?))
 }
+
+#[get("/tags/suggest?<prefix>&<limit>")]
+pub async fn suggest(
+    db: &Db,
+    user: User,
+    prefix: Option<&str>,
+    limit: Option<u32>,
+) -> ApiResult<Vec<tags::TagSuggestion>> {
+    let prefix = prefix.unwrap_or_default();
+    Ok(Json(tags::suggest(db.as_ref(), user.id, prefix, limit.unwrap_or(10)).await?))
+}
+
+#[get("/tags/graph?<min_weight>&<tags>&<format>")]
+pub async fn graph(
+    db: &Db,
+    user: User,
+    min_weight: Option<i64>,
+    tags: Option<&str>,
+    format: Option<&str>,
+) -> Result<GraphResponse, ApiErr<tags::TagError>> {
+    let graphml = match format.unwrap_or("json") {
+        "json" => false,
+        "graphml" => true,
+        _ => return Err(tags::TagError::UnknownFormat.into()),
+    };
+    let tags = super::article::split_tags(tags);
+    let graph = tags::graph(db.as_ref(), user.id, min_weight.unwrap_or(1), &tags).await?;
+    if graphml {
+        let content_type = ContentType::new("application", "graphml+xml");
+        Ok(GraphResponse::GraphMl(graph.to_graphml(), content_type))
+    } else {
+        Ok(GraphResponse::Json(Json(graph)))
+    }
+}
+
+#[patch("/tags/<id>", data = "<patch>")]
+pub async fn update(
+    db: &Db,
+    events: &Events,
+    user: User,
+    id: i32,
+    patch: Json<tags::TagPatch<'_>>,
+) -> ApiResult<()> {
+    tags::update(db.as_ref(), events.as_ref(), user.id, id, patch.0).await?;
+    Ok(Json(()))
+}
+
+#[delete("/tags/<id>")]
+pub async fn delete(db: &Db, events: &Events, user: User, id: i32) -> ApiResult<()> {
+    tags::delete(db.as_ref(), events.as_ref(), user.id, id).await?;
+    Ok(Json(()))
+}
+
+#[post("/tags/<id>/merge", data = "<info>")]
+pub async fn merge(
+    db: &Db,
+    events: &Events,
+    user: User,
+    id: i32,
+    info: Json<MergeInfo>,
+) -> ApiResult<()> {
+    tags::merge(db.as_ref(), events.as_ref(), user.id, id, info.into).await?;
+    Ok(Json(()))
+}
+
+#[get("/tags/<id>/aliases")]
+pub async fn aliases(db: &Db, user: User, id: i32) -> ApiResult<Vec<tags::TagAlias>> {
+    Ok(Json(tags::aliases(db.as_ref(), user.id, id).await?))
+}
+
+#[post("/tags/<id>/aliases", data = "<info>")]
+pub async fn create_alias(
+    db: &Db,
+    events: &Events,
+    user: User,
+    id: i32,
+    info: Json<AliasInfo<'_>>,
+) -> ApiResult<i32> {
+    let alias = tags::create_alias(db.as_ref(), events.as_ref(), user.id, id, info.name).await?;
+    Ok(Json(alias))
+}
+
+#[delete("/tags/<id>/aliases/<alias>")]
+pub async fn delete_alias(
+    db: &Db,
+    events: &Events,
+    user: User,
+    id: i32,
+    alias: i32,
+) -> ApiResult<()> {
+    tags::delete_alias(db.as_ref(), events.as_ref(), user.id, id, alias).await?;
+    Ok(Json(()))
+}
@@ -0,0 +1,55 @@
+use rocket::serde::json::Json;
+use super::{ApiErr, AsHttpStatus, Db, Events, LinkAnalyzer, User};
+use crate::db::{article, tasks};
+
+// ========================== TYPES =======================
+
+type ApiResult<T> = Result<Json<T>, ApiErr<tasks::TaskError>>;
+
+// ========================== ERRORS ======================
+
+impl AsHttpStatus for tasks::TaskError {
+    fn status(&self) -> rocket::http::Status {
+        use rocket::http::Status;
+        use tasks::TaskError;
+        match &self {
+            TaskError::NotFound => Status::NotFound,
+            TaskError::Article(e) => e.status(),
+            TaskError::Internal(_) => Status::InternalServerError,
+        }
+    }
+}
+
+// ========================= RESPONDERS ===================
+
+#[get("/tasks?<tags>&<all_tags>")]
+pub async fn list(
+    db: &Db,
+    user: User,
+    tags: Option<&str>,
+    all_tags: Option<bool>,
+) -> ApiResult<Vec<tasks::Task>> {
+    // tags are given by name or id
+    let tags = super::article::split_tags(tags);
+    let tags = article::resolve_tags(db.as_ref(), user.id, &tags)
+        .await
+        .map_err(tasks::TaskError::from)?;
+    let options = tasks::ListOptions {
+        tags,
+        all_tags: all_tags.unwrap_or(false),
+    };
+    Ok(Json(tasks::list(db.as_ref(), user.id, options).await?))
+}
+
+#[post("/tasks/<id>/toggle")]
+pub async fn toggle(
+    db: &Db,
+    link_analyzer: &LinkAnalyzer,
+    events: &Events,
+    user: User,
+    id: i32,
+) -> ApiResult<bool> {
+    Ok(Json(
+        tasks::toggle(db.as_ref(), link_analyzer.as_ref(), events.as_ref(), user.id, id).await?,
+    ))
+}
@@ -0,0 +1,75 @@
+use rocket::serde::{json::Json, Deserialize};
+use super::{ApiErr, AsHttpStatus, Db, Events, LinkAnalyzer, User};
+use crate::db::{article::ArticlePreview, templates};
+
+// ========================== TYPES =======================
+
+type ApiResult<T> = Result<Json<T>, ApiErr<templates::TemplateError>>;
+
+#[derive(Deserialize)]
+pub struct InstantiateInfo<'r> {
+    pub title: Option<&'r str>,
+}
+
+// ========================== ERRORS ======================
+
+impl AsHttpStatus for templates::TemplateError {
+    fn status(&self) -> rocket::http::Status {
+        use rocket::http::Status;
+        use templates::TemplateError;
+        match &self {
+            TemplateError::NotTemplate => Status::BadRequest,
+            TemplateError::Article(e) => e.status(),
+            TemplateError::Internal(_) => Status::InternalServerError,
+        }
+    }
+}
+
+// ========================= RESPONDERS ===================
+
+#[get("/templates")]
+pub async fn list(db: &Db, user: User) -> ApiResult<Vec<ArticlePreview>> {
+    Ok(Json(templates::list(db.as_ref(), user.id).await?))
+}
+
+#[post("/article/<id>/duplicate")]
+pub async fn duplicate(
+    db: &Db,
+    link_analyzer: &LinkAnalyzer,
+    events: &Events,
+    user: User,
+    id: i32,
+) -> ApiResult<i32> {
+    Ok(Json(
+        templates::duplicate(
+            db.as_ref(),
+            link_analyzer.as_ref(),
+            events.as_ref(),
+            user.id,
+            id,
+        )
+        .await?,
+    ))
+}
+
+#[post("/templates/<id>/instantiate", data = "<info>")]
+pub async fn instantiate(
+    db: &Db,
+    link_analyzer: &LinkAnalyzer,
+    events: &Events,
+    user: User,
+    id: i32,
+    info: Option<Json<InstantiateInfo<'_>>>,
+) -> ApiResult<i32> {
+    let title = info.as_ref().and_then(|info| info.title);
+    Ok(Json(
+        templates::instantiate(
+            db.as_ref(),
+            link_analyzer.as_ref(),
+            events.as_ref(),
+            user.id,
+            id,
+            title,
+        ).await?,
+    ))
+}
@@ -0,0 +1,35 @@
+use rocket::serde::json::Json;
+use super::{ApiErr, AsHttpStatus, Db, User, VersionsConfig};
+use crate::db::versions;
+
+// ========================== TYPES =======================
+
+type ApiResult<T> = Result<Json<T>, ApiErr<versions::VersionError>>;
+
+// ========================== ERRORS ======================
+
+impl AsHttpStatus for versions::VersionError {
+    fn status(&self) -> rocket::http::Status {
+        use rocket::http::Status;
+        use versions::VersionError;
+        match &self {
+            VersionError::Disabled => Status::NotFound,
+            VersionError::Article(e) => e.status(),
+            VersionError::Git(_) => Status::InternalServerError,
+        }
+    }
+}
+
+// ========================= RESPONDERS ===================
+
+#[get("/article/<id>/history")]
+pub async fn history(
+    db: &Db,
+    config: &VersionsConfig,
+    user: User,
+    id: i32,
+) -> ApiResult<Vec<versions::Version>> {
+    Ok(Json(
+        versions::history(db.as_ref(), config.as_ref(), user.id, id).await?,
+    ))
+}
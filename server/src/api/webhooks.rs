@@ -0,0 +1,56 @@
+use rocket::serde::json::Json;
+use super::{ApiErr, AsHttpStatus, Db, User};
+use crate::db::webhooks;
+
+// ========================== TYPES =======================
+
+type ApiResult<T> = Result<Json<T>, ApiErr<webhooks::WebhookError>>;
+
+// ========================== ERRORS ======================
+
+impl AsHttpStatus for webhooks::WebhookError {
+    fn status(&self) -> rocket::http::Status {
+        use rocket::http::Status;
+        use webhooks::WebhookError;
+        match &self {
+            WebhookError::NotFound => Status::NotFound,
+            WebhookError::BadUrl => Status::BadRequest,
+            WebhookError::UnknownEvent => Status::BadRequest,
+            WebhookError::Internal(_) => Status::InternalServerError,
+        }
+    }
+}
+
+// ========================= RESPONDERS ===================
+
+#[get("/webhooks")]
+pub async fn list(db: &Db, user: User) -> ApiResult<Vec<webhooks::Webhook>> {
+    Ok(Json(webhooks::list(db.as_ref(), user.id).await?))
+}
+
+#[post("/webhooks", data = "<webhook>")]
+pub async fn create(
+    db: &Db,
+    user: User,
+    webhook: Json<webhooks::WebhookInsert>,
+) -> ApiResult<webhooks::WebhookCreated> {
+    Ok(Json(webhooks::create(db.as_ref(), user.id, webhook.0).await?))
+}
+
+#[delete("/webhooks/<id>")]
+pub async fn delete(db: &Db, user: User, id: i32) -> ApiResult<()> {
+    webhooks::delete(db.as_ref(), user.id, id).await?;
+    Ok(Json(()))
+}
+
+#[get("/webhooks/<id>/deliveries?<from>&<limit>")]
+pub async fn deliveries(
+    db: &Db,
+    user: User,
+    id: i32,
+    from: Option<u32>,
+    limit: Option<u32>,
+) -> ApiResult<Vec<webhooks::Delivery>> {
+    let page = (from.unwrap_or(0), limit.unwrap_or(50));
+    Ok(Json(webhooks::deliveries(db.as_ref(), user.id, id, page).await?))
+}
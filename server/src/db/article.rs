@@ -1,17 +1,38 @@
 use chrono::NaiveDateTime;
 use rocket::serde::{Deserialize, Serialize};
+use super::crypto;
+use super::events::{EventKind, Events};
 use super::links::Analyzer as LinkAnalyzer;
+use super::tags;
+use super::versions::Change;
 use super::Db;
-use crate::utils::extractor;
+use crate::utils::{self, extractor};
+use sqlx::{Postgres, Transaction};
+use std::collections::HashSet;
 
 // ========================== TYPES =======================
 
+const MAX_TITLE_LEN: usize = 200;
+const MAX_CONTENT_LEN: usize = 1 << 20;
+const MAX_TAGS: usize = 32;
+const MAX_TAG_LEN: usize = 50;
+const TAG_SYMBOLS: &str = " -_./+#";
+const SUGGESTED_TAGS: u32 = 10;
+/// Minimal score of suggestions applied by `auto_tag`
+const AUTO_TAG_THRESHOLD: f64 = 0.25;
+const AUTO_TAG_LIMIT: usize = 5;
+/// Language value clearing the override
+const AUTO_LANGUAGE: &str = "auto";
+
 #[derive(Debug, sqlx::FromRow, Serialize)]
 pub struct Article {
     pub id: i32,
     pub title: String,
     pub content: String,
     pub tags: Vec<String>,
+    /// Language override, detected from the content if missing
+    pub language: Option<String>,
+    pub notebook_id: Option<i32>,
     pub created_on: NaiveDateTime,
     pub updated_on: NaiveDateTime,
 }
@@ -22,6 +43,7 @@ pub struct ArticlePreview {
     pub title: String,
     pub preview: String,
     pub tags: Vec<String>,
+    pub notebook_id: Option<i32>,
     pub created_on: NaiveDateTime,
     pub updated_on: NaiveDateTime,
 }
@@ -32,6 +54,12 @@ pub struct ArticleInsert<'a> {
     pub title: &'a str,
     pub content: String,
     pub tags: Vec<&'a str>,
+    /// Overrides the detected language. Updates keep the stored override
+    /// if missing, `auto` returns to detection.
+    pub language: Option<&'a str>,
+    /// Adds confident tag suggestions
+    #[serde(default)]
+    pub auto_tag: bool,
 }
 
 #[derive(Debug)]
@@ -40,8 +68,10 @@ pub struct ListOptions {
     pub limit: u32,
     pub tags: Vec<i32>,
     pub all_tags: bool,
+    pub exclude_tags: Vec<i32>,
     pub sort_by_created: bool,
     pub query: String,
+    pub notebook: Option<i32>,
 }
 
 // ========================== ERRORS ======================
@@ -52,6 +82,12 @@ pub enum ArticleError {
     NotFound,
     #[error("Bad content")]
     BadContent,
+    #[error("Invalid fields")]
+    Invalid(Vec<FieldError>),
+    #[error("Unknown tags")]
+    UnknownTags(Vec<String>),
+    #[error("Changed since base seq")]
+    Conflict,
     #[error("Internal")]
     Internal(
         #[source]
@@ -61,6 +97,70 @@ pub enum ArticleError {
     ),
 }
 
+#[derive(Debug, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub code: &'static str,
+    pub message: String,
+}
+
+// ========================== QUERIES =====================
+
+/// Filter parameters of list as the `filters` relation
+const LIST_PARAMS: &str = "
+        WITH filters AS (
+            SELECT 0 as key, $1::int as user_id, $4::int[] as or_tags, $5::int[] as all_tags,
+                $7::text as query, $8::int as notebook, $9::int[] as exclude_tags
+        )
+        ";
+
+/// Matches articles against every row of the `filters` relation,
+/// grouped by filter key and article.
+/// Filter tags match themselves and their descendants.
+pub const LIST_FILTER: &str = "
+        FROM filters f
+                JOIN articles a ON a.user_id = f.user_id
+                LEFT JOIN article_tags at ON at.article_id = a.id
+                LEFT JOIN tags t ON at.tag_id = t.id
+        WHERE CASE WHEN coalesce(f.query, '') = ''
+            THEN TRUE
+            ELSE plainto_tsquery(a.language, f.query) @@ a.search_vector 
+        END 
+            AND (f.notebook IS NULL OR a.notebook_id IN (
+                WITH RECURSIVE sub AS (
+                    SELECT id FROM notebooks WHERE id = f.notebook
+                    UNION ALL
+                    SELECT n.id FROM notebooks n JOIN sub ON n.parent_id = sub.id
+                )
+                SELECT id FROM sub
+            ))
+            AND (CARDINALITY(f.or_tags) = 0 OR EXISTS(
+                SELECT 1 FROM article_tags fat
+                    JOIN tags c ON c.id = fat.tag_id
+                    JOIN tags p ON p.id = ANY(f.or_tags) AND p.user_id = f.user_id
+                WHERE fat.article_id = a.id
+                    AND (c.id = p.id OR starts_with(c.name, p.name || '/'))
+            ))
+            AND NOT EXISTS(
+                SELECT 1 FROM UNNEST(f.all_tags) ft (id)
+                WHERE NOT EXISTS(
+                    SELECT 1 FROM article_tags fat
+                        JOIN tags c ON c.id = fat.tag_id
+                        JOIN tags p ON p.id = ft.id AND p.user_id = f.user_id
+                    WHERE fat.article_id = a.id
+                        AND (c.id = p.id OR starts_with(c.name, p.name || '/'))
+                )
+            )
+            AND NOT EXISTS(
+                SELECT 1 FROM article_tags fat
+                    JOIN tags c ON c.id = fat.tag_id
+                    JOIN tags p ON p.id = ANY(f.exclude_tags) AND p.user_id = f.user_id
+                WHERE fat.article_id = a.id
+                    AND (c.id = p.id OR starts_with(c.name, p.name || '/'))
+            )
+        GROUP BY f.key, a.id
+        ";
+
 // ========================== FUNCTIONS ===================
 
 pub async fn list(
@@ -73,40 +173,30 @@ pub async fn list(
     } else {
         (opt.tags, vec![])
     };
-    Ok(sqlx::query_as(
-        "SELECT a.id, a.title, a.created_on, a.updated_on,
+    let previews = sqlx::query_as(concatcp!(
+        LIST_PARAMS,
+        "SELECT a.id, a.title, a.notebook_id, a.created_on, a.updated_on,
         ARRAY_REMOVE(ARRAY_AGG(t.name), NULL) as tags,
         CASE WHEN coalesce($7, '') = ''
             THEN 0
             ELSE ts_rank_cd(search_vector, plainto_tsquery(a.language, $7), 32)
         END AS rank,
-        CASE WHEN coalesce($7, '') = ''
+        CASE WHEN coalesce($7, '') = '' OR a.raw_text LIKE 'enc:v1:%'
             THEN a.preview
             ELSE ts_headline(a.raw_text, plainto_tsquery(a.language, $7),
                 'StartSel=**, StopSel=**,
                 MaxWords=30, MinWords=15,
                 MaxFragments=5')
-        END AS preview
-        FROM articles a
-                LEFT JOIN article_tags at ON at.article_id = a.id
-                LEFT JOIN tags t ON at.tag_id = t.id
-        WHERE a.user_id = $1 
-            AND CASE WHEN coalesce($7, '') = ''
-            THEN TRUE
-            ELSE plainto_tsquery(a.language, $7) @@ a.search_vector 
-        END 
-        GROUP BY a.id
-	    HAVING 
-		    (CARDINALITY($4::int[]) = 0 OR $4::int[] && ARRAY_AGG(t.id))
-		    AND (CARDINALITY($5::int[]) = 0 OR $5::int[] <@ ARRAY_AGG(t.id))
-        ORDER BY 
+        END AS preview",
+        LIST_FILTER,
+        "ORDER BY 
             rank DESC,
             CASE WHEN ($6)
                 THEN a.created_on
                 ELSE a.updated_on 
             END DESC
         LIMIT $2 OFFSET $3",
-    )
+    ))
     .bind(user)
     .bind(opt.limit)
     .bind(opt.offset)
@@ -114,14 +204,18 @@ pub async fn list(
     .bind(all_tags)
     .bind(opt.sort_by_created)
     .bind(opt.query)
+    .bind(opt.notebook)
+    .bind(opt.exclude_tags)
     .fetch_all(db)
-    .await?)
+    .await?;
+    Ok(decrypt_previews(db, user, previews).await?)
 }
 
 pub async fn get(db: &Db, user: i32, id: i32) -> Result<Article, ArticleError> {
     let article = sqlx::query_as::<_, Article>(
         "
-        SELECT a.id, a.title, a.content, a.created_on, a.updated_on, 
+        SELECT a.id, a.title, a.content, a.notebook_id, a.created_on, a.updated_on, 
+            a.language_override::text as language,
             ARRAY_REMOVE(ARRAY_AGG(t.name), NULL) as tags
         FROM articles a 
             LEFT JOIN article_tags at ON at.article_id = a.id 
@@ -133,120 +227,473 @@ pub async fn get(db: &Db, user: i32, id: i32) -> Result<Article, ArticleError> {
     .bind(user)
     .fetch_optional(db)
     .await?;
-    article.ok_or(ArticleError::NotFound)
+    let mut article = article.ok_or(ArticleError::NotFound)?;
+    article.content = crypto::decrypt(db, user, article.content).await?;
+    Ok(article)
 }
 
 pub async fn create(
     db: &Db,
     link_analyzer: &LinkAnalyzer,
+    events: &Events,
     user: i32,
     article: ArticleInsert<'_>,
 ) -> Result<i32, ArticleError> {
-    if !validate_content(&article) {
-        return Err(ArticleError::BadContent);
-    }
+    let mut tx = db.begin().await?;
+    validate_content(&mut tx, user, None, &article).await?;
+    let language = get_language(None, article.language)?;
     // insert values
-    let info = extractor::extract_article(&article.content);
+    let info = extractor::extract_article(&article.content, language);
+    let stored = encrypt_values(db, user, &article.content, &info).await?;
     let id: i32 = sqlx::query_scalar(
         "
-        INSERT INTO articles (user_id, title, content, raw_text, preview, language)
-        VALUES ($1, $2, $3, $4, $5, CAST($6 AS regconfig))
+        INSERT INTO articles (user_id, title, content, raw_text, preview, language, search_vector,
+            language_override)
+        VALUES ($1, $2, $3, $4, $5, CAST($6 AS regconfig),
+            to_tsvector(CAST($6 AS regconfig), LOWER($2) || ' ' || LOWER($7)),
+            CAST($8 AS regconfig))
         RETURNING id",
     )
     .bind(user)
     .bind(article.title)
-    .bind(article.content)
-    .bind(info.text)
-    .bind(info.preview)
+    .bind(stored.content)
+    .bind(stored.raw_text)
+    .bind(stored.preview)
     .bind(info.language)
-    .fetch_one(db)
+    .bind(&info.text)
+    .bind(language)
+    .fetch_one(&mut *tx)
     .await?;
-    // update tags
-    super::tags::update_article_tags(db, user, id, &article.tags).await?;
+    update_related(db, &mut tx, user, id, &article, &info).await?;
+    tx.commit().await?;
     // update links
     super::links::update_article_links(link_analyzer, id, user, &info.links).await?;
+    // commit version
+    super::versions::commit(db, user, id, Change::Created).await;
+    // notify subscribers
+    events.emit(user, EventKind::ArticleCreated { id }).await;
     Ok(id)
 }
 
+/// Saves the article. With a `base_seq` the article is only saved if it
+/// still has that seq, otherwise the update fails with `Conflict`.
 pub async fn update(
     db: &Db,
     link_analyzer: &LinkAnalyzer,
+    events: &Events,
     user: i32,
     article: ArticleInsert<'_>,
+    base_seq: Option<i64>,
 ) -> Result<(), ArticleError> {
     // check id
     let id = article.id.ok_or(ArticleError::BadContent)?;
-    if !validate_content(&article) {
-        return Err(ArticleError::BadContent);
-    }
     // check user access
-    match get_user_id(db, id).await? {
-        Some(stored_user) if stored_user == user => (),
-        _ => return Err(ArticleError::NotFound),
-    }
+    let mut tx = db.begin().await?;
+    let stored_article = lock_article(&mut tx, user, id).await?;
+    validate_content(&mut tx, user, Some(&stored_article), &article).await?;
+    check_seq(&stored_article, base_seq)?;
+    let language = get_language(stored_article.language.as_deref(), article.language)?;
     // update values
-    let info = extractor::extract_article(&article.content);
+    let info = extractor::extract_article(&article.content, language);
+    let stored = encrypt_values(db, user, &article.content, &info).await?;
     sqlx::query(
         "
         UPDATE articles SET 
-        title = $2, content = $3, raw_text = $4, preview = $5, language = CAST($6 AS regconfig)
+        title = $2, content = $3, raw_text = $4, preview = $5, language = CAST($6 AS regconfig),
+        search_vector = to_tsvector(CAST($6 AS regconfig), LOWER($2) || ' ' || LOWER($7)),
+        language_override = CAST($8 AS regconfig)
         WHERE id = $1
         ",
     )
     .bind(id)
     .bind(article.title)
-    .bind(article.content)
-    .bind(info.text)
-    .bind(info.preview)
+    .bind(stored.content)
+    .bind(stored.raw_text)
+    .bind(stored.preview)
     .bind(info.language)
-    .execute(db)
+    .bind(&info.text)
+    .bind(language)
+    .execute(&mut *tx)
     .await?;
-    // update tags
-    super::tags::update_article_tags(db, user, id, &article.tags).await?;
+    update_related(db, &mut tx, user, id, &article, &info).await?;
+    tx.commit().await?;
     // update links
     super::links::update_article_links(link_analyzer, id, user, &info.links).await?;
+    // commit version
+    super::versions::commit(db, user, id, Change::Updated).await;
+    // notify subscribers
+    events.emit(user, EventKind::ArticleUpdated { id }).await;
     Ok(())
 }
 
-pub async fn delete(db: &Db, user: i32, id: i32) -> Result<(), ArticleError> {
-    match get_user_id(db, id).await? {
-        Some(stored_user) if stored_user == user => (),
-        _ => return Err(ArticleError::NotFound),
-    };
+/// Resolves tag names and aliases case-insensitively, the same way tags
+/// are resolved on save. Unknown names that are numbers are read as ids.
+pub async fn resolve_tags(db: &Db, user: i32, tags: &[&str]) -> Result<Vec<i32>, ArticleError> {
+    if tags.is_empty() {
+        return Ok(vec![]);
+    }
+    let names = tags::canonical_names(db, user, tags).await?;
+    let resolved: Vec<(String, Option<i32>)> = sqlx::query_as(
+        "
+        SELECT f.value, COALESCE(
+            (SELECT id FROM tags WHERE user_id = $1 AND name = f.name),
+            (SELECT id FROM tags WHERE user_id = $1
+                AND id = CASE WHEN f.value ~ '^[0-9]{1,9}$' THEN f.value::int END)
+        )
+        FROM UNNEST($2::text[], $3::text[]) f (value, name)",
+    )
+    .bind(user)
+    .bind(tags)
+    .bind(names)
+    .fetch_all(db)
+    .await?;
+    let unknown: Vec<String> = resolved
+        .iter()
+        .filter(|(_, id)| id.is_none())
+        .map(|(value, _)| value.clone())
+        .collect();
+    if !unknown.is_empty() {
+        return Err(ArticleError::UnknownTags(unknown));
+    }
+    Ok(resolved.into_iter().filter_map(|(_, id)| id).collect())
+}
+
+/// Existing tags similar to the article content, best first
+pub async fn suggested_tags(
+    db: &Db,
+    user: i32,
+    id: i32,
+) -> Result<Vec<tags::ScoredTag>, ArticleError> {
+    let article = get(db, user, id).await?;
+    let scored = tags::score_for_article(db, user, id, SUGGESTED_TAGS + MAX_TAGS as u32).await?;
+    Ok(scored
+        .into_iter()
+        .filter(|t| !article.tags.contains(&t.name))
+        .take(SUGGESTED_TAGS as usize)
+        .collect())
+}
+
+/// Deletes the article, `base_seq` is checked like on `update`
+pub async fn delete(
+    db: &Db,
+    events: &Events,
+    user: i32,
+    id: i32,
+    base_seq: Option<i64>,
+) -> Result<(), ArticleError> {
+    let mut tx = db.begin().await?;
+    let stored_article = lock_article(&mut tx, user, id).await?;
+    check_seq(&stored_article, base_seq)?;
     // update links
-    super::links::delete_article_links(db, id).await?;
+    super::links::delete_article_links(&mut tx, id).await?;
     // update tags
-    super::tags::update_article_tags(db, user, id, &[]).await?;
+    tags::update_article_tags(&mut tx, user, id, &[]).await?;
     // delete
     sqlx::query("DELETE FROM articles WHERE id = $1")
         .bind(id)
-        .execute(db)
+        .execute(&mut *tx)
         .await?;
+    tx.commit().await?;
+    // commit version
+    super::versions::commit(db, user, id, Change::Deleted).await;
+    // notify subscribers
+    events.emit(user, EventKind::ArticleDeleted { id }).await;
     Ok(())
 }
 
 // ========================== HELPERS =====================
 
-async fn get_user_id(db: &Db, id: i32) -> Result<Option<i32>, sqlx::Error> {
-    sqlx::query_scalar(
+/// Values of the locked article that an update is checked against
+#[derive(sqlx::FromRow)]
+struct StoredArticle {
+    title: String,
+    seq: i64,
+    language: Option<String>,
+    num_tags: i64,
+}
+
+/// Column values as stored, encrypted if enabled
+struct StoredValues {
+    content: String,
+    raw_text: String,
+    preview: String,
+}
+
+async fn encrypt_values(
+    db: &Db,
+    user: i32,
+    content: &str,
+    info: &extractor::Info,
+) -> Result<StoredValues, sqlx::Error> {
+    Ok(StoredValues {
+        content: crypto::encrypt(db, user, content).await?,
+        raw_text: crypto::encrypt(db, user, &info.text).await?,
+        preview: crypto::encrypt(db, user, &info.preview).await?,
+    })
+}
+
+/// Locks the article of the user until the end of the transaction
+async fn lock_article(
+    tx: &mut Transaction<'_, Postgres>,
+    user: i32,
+    id: i32,
+) -> Result<StoredArticle, ArticleError> {
+    sqlx::query_as::<_, StoredArticle>(
         "
-        SELECT user_id FROM articles
-        WHERE id = $1",
+        SELECT a.title, a.seq, a.language_override::text as language,
+            (SELECT COUNT(*) FROM article_tags WHERE article_id = a.id) as num_tags
+        FROM articles a
+        WHERE a.id = $1 AND a.user_id = $2
+        FOR UPDATE",
     )
     .bind(id)
-    .fetch_optional(db)
-    .await
+    .bind(user)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(ArticleError::NotFound)
+}
+
+/// The locked article has to be unchanged since the base seq, if one is given
+fn check_seq(stored: &StoredArticle, base_seq: Option<i64>) -> Result<(), ArticleError> {
+    match base_seq {
+        Some(seq) if seq != stored.seq => Err(ArticleError::Conflict),
+        _ => Ok(()),
+    }
+}
+
+/// Writes the tags, tasks and revision of the saved article
+async fn update_related(
+    db: &Db,
+    tx: &mut Transaction<'_, Postgres>,
+    user: i32,
+    id: i32,
+    article: &ArticleInsert<'_>,
+    info: &extractor::Info,
+) -> Result<(), sqlx::Error> {
+    let article_tags = get_article_tags(tx, user, id, article).await?;
+    let article_tags: Vec<&str> = article_tags.iter().map(String::as_str).collect();
+    tags::update_article_tags(tx, user, id, &article_tags).await?;
+    super::tasks::update_article_tasks(db, tx, user, id, &info.tasks).await?;
+    super::revisions::record(db, tx, user, id, article.title, &article.content, &article_tags)
+        .await
+}
+
+/// Canonical names of the given tags followed by the automatic ones
+async fn get_article_tags(
+    tx: &mut Transaction<'_, Postgres>,
+    user: i32,
+    id: i32,
+    article: &ArticleInsert<'_>,
+) -> Result<Vec<String>, sqlx::Error> {
+    let mut names = tags::canonical_names(&mut *tx, user, &article.tags).await?;
+    let mut seen = HashSet::new();
+    names.retain(|name| seen.insert(name.clone()));
+    if article.auto_tag {
+        let auto_tags = auto_tags(tx, user, id, &names).await?;
+        names.extend(auto_tags);
+    }
+    Ok(names)
+}
+
+/// Confident suggestions that aren't among the tags yet
+async fn auto_tags(
+    tx: &mut Transaction<'_, Postgres>,
+    user: i32,
+    id: i32,
+    existing: &[String],
+) -> Result<Vec<String>, sqlx::Error> {
+    let room = MAX_TAGS.saturating_sub(existing.len()).min(AUTO_TAG_LIMIT);
+    let limit = (room + existing.len()) as u32;
+    let scored = tags::score_for_article(&mut *tx, user, id, limit).await?;
+    Ok(scored
+        .into_iter()
+        .filter(|t| t.score >= AUTO_TAG_THRESHOLD)
+        .filter(|t| !existing.contains(&t.name))
+        .map(|t| t.name)
+        .take(room)
+        .collect())
+}
+
+/// Decrypts previews read from the database
+pub async fn decrypt_previews(
+    db: &Db,
+    user: i32,
+    previews: Vec<ArticlePreview>,
+) -> Result<Vec<ArticlePreview>, sqlx::Error> {
+    let mut decrypted = Vec::with_capacity(previews.len());
+    for mut preview in previews {
+        preview.preview = crypto::decrypt(db, user, preview.preview).await?;
+        decrypted.push(preview);
+    }
+    Ok(decrypted)
+}
+
+/// Language override of the article, the stored one is kept if none is given
+fn get_language(
+    stored: Option<&str>,
+    language: Option<&str>,
+) -> Result<Option<&'static str>, ArticleError> {
+    let language = match language.or(stored) {
+        Some(language) if language.eq_ignore_ascii_case(AUTO_LANGUAGE) => return Ok(None),
+        Some(language) => language,
+        None => return Ok(None),
+    };
+    utils::supported_language(language)
+        .map(Some)
+        .ok_or_else(|| {
+            ArticleError::Invalid(vec![field_error(
+                "language",
+                "unsupported",
+                format!("Language {} is not supported", language),
+            )])
+        })
 }
 
-fn validate_content(
-    ArticleInsert {
+/// Collects the field errors of the article. Rules added later don't apply
+/// to stored values, so the stored title and known tags are accepted as is.
+async fn validate_content(
+    tx: &mut Transaction<'_, Postgres>,
+    user: i32,
+    stored: Option<&StoredArticle>,
+    article: &ArticleInsert<'_>,
+) -> Result<(), ArticleError> {
+    let known_tags: Vec<String> = sqlx::query_scalar(
+        "SELECT f.name FROM UNNEST($2::text[]) f (name)
+        WHERE EXISTS(SELECT 1 FROM tags WHERE user_id = $1 AND name = LOWER(f.name))
+            OR EXISTS(SELECT 1 FROM tag_aliases WHERE user_id = $1 AND name = LOWER(f.name))",
+    )
+    .bind(user)
+    .bind(&article.tags)
+    .fetch_all(&mut *tx)
+    .await?;
+    let ArticleInsert {
         title,
         content,
         tags,
         ..
-    }: &ArticleInsert<'_>,
-) -> bool {
-    (1..).contains(&title.len())
-        && (1..).contains(&content.len())
-        && tags.iter().all(|tag| !tag.is_empty())
+    } = article;
+    let mut errors = Vec::new();
+    let title_changed = stored.map(|s| s.title != *title).unwrap_or(true);
+    if title.is_empty() {
+        errors.push(field_error("title", "empty", "Title is empty".to_owned()));
+    } else if title_changed && title.chars().count() > MAX_TITLE_LEN {
+        errors.push(field_error(
+            "title",
+            "too_long",
+            format!("Title is longer than {} characters", MAX_TITLE_LEN),
+        ));
+    }
+    if content.is_empty() {
+        errors.push(field_error("content", "empty", "Content is empty".to_owned()));
+    } else if content.len() > MAX_CONTENT_LEN {
+        errors.push(field_error(
+            "content",
+            "too_large",
+            format!("Content is larger than {} bytes", MAX_CONTENT_LEN),
+        ));
+    }
+    let new_tags: Vec<(usize, &&str)> = tags
+        .iter()
+        .enumerate()
+        .filter(|(_, tag)| !known_tags.iter().any(|known| known == *tag))
+        .collect();
+    let stored_tags = stored.map(|s| s.num_tags as usize);
+    let tags_added = !new_tags.is_empty() || tags.len() > stored_tags.unwrap_or(tags.len());
+    if tags.len() > MAX_TAGS && tags_added {
+        errors.push(field_error(
+            "tags",
+            "too_many",
+            format!("Article has more than {} tags", MAX_TAGS),
+        ));
+    }
+    errors.extend(
+        new_tags
+            .into_iter()
+            .filter_map(|(i, tag)| validate_tag(format!("tags[{}]", i), tag)),
+    );
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ArticleError::Invalid(errors))
+    }
+}
+
+/// Checks length and characters of a tag name
+pub fn validate_tag(field: String, tag: &str) -> Option<FieldError> {
+    if tag.is_empty() {
+        Some(field_error(field, "empty", "Tag is empty".to_owned()))
+    } else if tag.chars().count() > MAX_TAG_LEN {
+        Some(field_error(
+            field,
+            "too_long",
+            format!("Tag is longer than {} characters", MAX_TAG_LEN),
+        ))
+    } else if tag.split(tags::SEPARATOR).any(|part| part.trim().is_empty()) {
+        Some(field_error(
+            field,
+            "empty_namespace",
+            "Tag has an empty namespace".to_owned(),
+        ))
+    } else if !tag
+        .chars()
+        .all(|c| c.is_alphanumeric() || TAG_SYMBOLS.contains(c))
+    {
+        Some(field_error(
+            field,
+            "bad_chars",
+            format!(
+                "Tag may only contain letters, digits, spaces and {}",
+                TAG_SYMBOLS.trim()
+            ),
+        ))
+    } else {
+        None
+    }
+}
+
+pub fn field_error(field: impl Into<String>, code: &'static str, message: String) -> FieldError {
+    FieldError {
+        field: field.into(),
+        code,
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{test_article, test_db, test_user};
+
+    #[rocket::async_test]
+    async fn filters_resolve_aliased_namespaces() {
+        let db = match test_db().await {
+            Some(db) => db,
+            None => return,
+        };
+        let user = test_user(&db).await;
+        test_article(&db, user, &["kubernetes/helm"]).await;
+        let ids: Vec<i32> = sqlx::query_scalar(
+            "SELECT id FROM tags WHERE user_id = $1 AND name IN ('kubernetes', 'kubernetes/helm')
+            ORDER BY name",
+        )
+        .bind(user)
+        .fetch_all(&db)
+        .await
+        .unwrap();
+        sqlx::query("INSERT INTO tag_aliases (user_id, tag_id, name) VALUES ($1, $2, 'k8s')")
+            .bind(user)
+            .bind(ids[0])
+            .execute(&db)
+            .await
+            .unwrap();
+
+        let resolved = resolve_tags(&db, user, &["K8s", "k8s/helm", "kubernetes/helm"])
+            .await
+            .unwrap();
+        assert_eq!(resolved, vec![ids[0], ids[1], ids[1]]);
+        match resolve_tags(&db, user, &["k8s/missing"]).await {
+            Err(ArticleError::UnknownTags(unknown)) => assert_eq!(unknown, vec!["k8s/missing"]),
+            other => panic!("expected unknown tags, got {:?}", other),
+        }
+    }
 }
@@ -0,0 +1,247 @@
+use super::Db;
+use aes_gcm::aead::{Aead, NewAead};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use once_cell::sync::OnceCell;
+use rand::Rng;
+use rocket::serde::Deserialize;
+use std::{collections::HashMap, convert::TryInto, sync::Mutex};
+
+// ========================== TYPES =======================
+
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+/// Marks encrypted values, everything else is read as plaintext
+const PREFIX: &str = "enc:v1:";
+
+type RawKey = [u8; KEY_LEN];
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// base64 encoded 32 byte key (e.g. `openssl rand -base64 32`).
+    /// Encryption is disabled without it.
+    pub master_key: Option<String>,
+}
+
+/// Master key and cache of unwrapped per-user data keys
+struct Keyring {
+    master: RawKey,
+    data_keys: Mutex<HashMap<i32, RawKey>>,
+}
+
+impl Keyring {
+    fn new(master: RawKey) -> Self {
+        Keyring {
+            master,
+            data_keys: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+static KEYRING: OnceCell<Keyring> = OnceCell::new();
+
+#[derive(Debug, thiserror::Error)]
+pub enum CryptoError {
+    #[error("Bad key")]
+    BadKey,
+    #[error("Encrypted value without master key")]
+    Disabled,
+    #[error("Decryption failed")]
+    Decrypt,
+}
+
+// ========================== FUNCTIONS ===================
+
+pub fn init(config: Config) -> Result<(), CryptoError> {
+    let master = match config.master_key {
+        Some(key) => parse_key(&key)?,
+        None => return Ok(()),
+    };
+    KEYRING
+        .set(Keyring::new(master))
+        .map_err(|_| CryptoError::BadKey)
+}
+
+pub fn is_enabled() -> bool {
+    KEYRING.get().is_some()
+}
+
+/// Encrypts the value with the user data key if encryption is enabled
+pub async fn encrypt(db: &Db, user: i32, plaintext: &str) -> Result<String, sqlx::Error> {
+    match KEYRING.get() {
+        Some(keyring) => encrypt_with(db, keyring, user, plaintext).await,
+        None => Ok(plaintext.to_owned()),
+    }
+}
+
+/// Decrypts the value if it was encrypted, plaintext is passed through
+pub async fn decrypt(db: &Db, user: i32, stored: String) -> Result<String, sqlx::Error> {
+    decrypt_with(db, KEYRING.get(), user, stored).await
+}
+
+/// Re-wraps all data keys with the new master key
+pub async fn rotate_master_key(
+    db: &Db,
+    old_key: &str,
+    new_key: &str,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let (old_key, new_key) = (parse_key(old_key)?, parse_key(new_key)?);
+    let mut tx = db.begin().await?;
+    let wrapped: Vec<(i32, String)> =
+        sqlx::query_as("SELECT user_id, wrapped_key FROM user_keys FOR UPDATE")
+            .fetch_all(&mut tx)
+            .await?;
+    for (user, wrapped_key) in &wrapped {
+        let data_key = to_key(open(&old_key, wrapped_key)?)?;
+        sqlx::query("UPDATE user_keys SET wrapped_key = $2 WHERE user_id = $1")
+            .bind(user)
+            .bind(seal(&new_key, &data_key))
+            .execute(&mut tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(wrapped.len())
+}
+
+// ========================== HELPERS =====================
+
+async fn encrypt_with(
+    db: &Db,
+    keyring: &Keyring,
+    user: i32,
+    plaintext: &str,
+) -> Result<String, sqlx::Error> {
+    let key = data_key(db, keyring, user).await?;
+    Ok(format!("{}{}", PREFIX, seal(&key, plaintext.as_bytes())))
+}
+
+async fn decrypt_with(
+    db: &Db,
+    keyring: Option<&Keyring>,
+    user: i32,
+    stored: String,
+) -> Result<String, sqlx::Error> {
+    let sealed = match stored.strip_prefix(PREFIX) {
+        Some(sealed) => sealed,
+        None => return Ok(stored),
+    };
+    let keyring = keyring.ok_or_else(|| decode_error(CryptoError::Disabled))?;
+    let key = data_key(db, keyring, user).await?;
+    let plaintext = open(&key, sealed).map_err(decode_error)?;
+    String::from_utf8(plaintext).map_err(|e| sqlx::Error::Decode(Box::new(e)))
+}
+
+async fn data_key(db: &Db, keyring: &Keyring, user: i32) -> Result<RawKey, sqlx::Error> {
+    if let Some(key) = keyring.data_keys.lock().unwrap().get(&user) {
+        return Ok(*key);
+    }
+    let generated: RawKey = rand::thread_rng().gen();
+    // keep the stored key if another request created it first
+    sqlx::query(
+        "INSERT INTO user_keys (user_id, wrapped_key) VALUES ($1, $2)
+        ON CONFLICT DO NOTHING",
+    )
+    .bind(user)
+    .bind(seal(&keyring.master, &generated))
+    .execute(db)
+    .await?;
+    let wrapped: String =
+        sqlx::query_scalar("SELECT wrapped_key FROM user_keys WHERE user_id = $1")
+            .bind(user)
+            .fetch_one(db)
+            .await?;
+    let key = open(&keyring.master, &wrapped)
+        .and_then(to_key)
+        .map_err(decode_error)?;
+    keyring.data_keys.lock().unwrap().insert(user, key);
+    Ok(key)
+}
+
+/// AES-256-GCM, returns base64 of nonce and ciphertext
+fn seal(key: &RawKey, plaintext: &[u8]) -> String {
+    let cipher = Aes256Gcm::new(&Key::from(*key));
+    let nonce: [u8; NONCE_LEN] = rand::thread_rng().gen();
+    let mut sealed = nonce.to_vec();
+    sealed.extend(
+        cipher
+            .encrypt(&Nonce::from(nonce), plaintext)
+            .expect("AES-GCM encryption failed"),
+    );
+    base64::encode(sealed)
+}
+
+fn open(key: &RawKey, sealed: &str) -> Result<Vec<u8>, CryptoError> {
+    let sealed = base64::decode(sealed).map_err(|_| CryptoError::Decrypt)?;
+    if sealed.len() < NONCE_LEN {
+        return Err(CryptoError::Decrypt);
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    let nonce: [u8; NONCE_LEN] = nonce.try_into().expect("Nonce has NONCE_LEN bytes");
+    let cipher = Aes256Gcm::new(&Key::from(*key));
+    cipher
+        .decrypt(&Nonce::from(nonce), ciphertext)
+        .map_err(|_| CryptoError::Decrypt)
+}
+
+fn parse_key(key: &str) -> Result<RawKey, CryptoError> {
+    to_key(base64::decode(key).map_err(|_| CryptoError::BadKey)?)
+}
+
+fn to_key(bytes: Vec<u8>) -> Result<RawKey, CryptoError> {
+    let mut key = [0u8; KEY_LEN];
+    if bytes.len() != KEY_LEN {
+        return Err(CryptoError::BadKey);
+    }
+    key.copy_from_slice(&bytes);
+    Ok(key)
+}
+
+fn decode_error(e: CryptoError) -> sqlx::Error {
+    sqlx::Error::Decode(Box::new(e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{test_db, test_user};
+
+    const OLD_KEY: RawKey = [1; KEY_LEN];
+    const NEW_KEY: RawKey = [2; KEY_LEN];
+
+    #[rocket::async_test]
+    async fn values_are_encrypted_and_survive_rotation() {
+        let db = match test_db().await {
+            Some(db) => db,
+            None => return,
+        };
+        // rotation re-wraps every key, drop those of earlier runs
+        sqlx::query("DELETE FROM user_keys").execute(&db).await.unwrap();
+        let user = test_user(&db).await;
+
+        let old = Keyring::new(OLD_KEY);
+        let stored = encrypt_with(&db, &old, user, "secret").await.unwrap();
+        assert!(stored.starts_with(PREFIX));
+        assert!(!stored.contains("secret"));
+        let decrypted = decrypt_with(&db, Some(&old), user, stored.clone()).await;
+        assert_eq!(decrypted.unwrap(), "secret");
+        let plain = decrypt_with(&db, Some(&old), user, "plain".to_owned()).await;
+        assert_eq!(plain.unwrap(), "plain");
+        assert!(decrypt_with(&db, None, user, stored.clone()).await.is_err());
+        let tampered = format!("{}{}", PREFIX, seal(&[3; KEY_LEN], b"secret"));
+        assert!(decrypt_with(&db, Some(&old), user, tampered).await.is_err());
+
+        let rotated = rotate_master_key(&db, &base64::encode(OLD_KEY), &base64::encode(NEW_KEY))
+            .await
+            .unwrap();
+        assert_eq!(rotated, 1);
+        let new = Keyring::new(NEW_KEY);
+        let decrypted = decrypt_with(&db, Some(&new), user, stored.clone()).await;
+        assert_eq!(decrypted.unwrap(), "secret");
+        // without cached data keys the old master key can't unwrap them anymore
+        let old = Keyring::new(OLD_KEY);
+        assert!(decrypt_with(&db, Some(&old), user, stored).await.is_err());
+        assert!(rotate_master_key(&db, &base64::encode(OLD_KEY), &base64::encode(NEW_KEY))
+            .await
+            .is_err());
+    }
+}
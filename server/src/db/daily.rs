@@ -0,0 +1,155 @@
+use super::article::{self, Article, ArticleError, ArticleInsert};
+use super::events::Events;
+use super::links::Analyzer as LinkAnalyzer;
+use super::Db;
+use crate::utils;
+use chrono::format::{Item, StrftimeItems};
+use chrono::NaiveDate;
+use rocket::serde::{Deserialize, Serialize};
+
+// ========================== TYPES =======================
+
+pub const DAILY_TAG: &str = "daily";
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// chrono format string for the note title
+    pub title: String,
+    /// markdown content, supports {{date}}, {{title}} and {{user}}
+    pub template: String,
+    /// Language of new notes, detected from the content if missing
+    pub language: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            title: "%Y-%m-%d".to_owned(),
+            template: "# {{title}}\n\n".to_owned(),
+            language: None,
+        }
+    }
+}
+
+impl Config {
+    /// Checks the title format, chrono panics on invalid ones
+    pub fn validate(&self) -> Result<(), String> {
+        if StrftimeItems::new(&self.title).any(|item| item == Item::Error) {
+            return Err(format!("Invalid daily title format {}", self.title));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, sqlx::FromRow, Serialize)]
+pub struct DailyEntry {
+    pub day: NaiveDate,
+    pub article_id: i32,
+}
+
+// ========================== ERRORS ======================
+
+#[derive(Debug, thiserror::Error, Serialize)]
+pub enum DailyError {
+    #[error("Bad date")]
+    BadDate,
+    #[error(transparent)]
+    Article(#[from] ArticleError),
+    #[error("Internal")]
+    Internal(
+        #[from]
+        #[source]
+        #[serde(skip)]
+        sqlx::Error,
+    ),
+}
+
+// ========================== FUNCTIONS ===================
+
+/// Returns the journal article for the day, creating it if needed
+pub async fn get_or_create(
+    db: &Db,
+    link_analyzer: &LinkAnalyzer,
+    events: &Events,
+    config: &Config,
+    user: i32,
+    day: NaiveDate,
+) -> Result<Article, DailyError> {
+    if let Some(id) = get_article_id(db, user, day).await? {
+        return Ok(article::get(db, user, id).await?);
+    }
+    // claims the day, concurrent requests wait here until the claim is committed
+    let mut tx = db.begin().await?;
+    let claimed = sqlx::query(
+        "INSERT INTO daily_notes (user_id, day) VALUES ($1, $2)
+        ON CONFLICT DO NOTHING",
+    )
+    .bind(user)
+    .bind(day)
+    .execute(&mut tx)
+    .await?
+    .rows_affected();
+    // another request created the note first
+    if claimed == 0 {
+        tx.rollback().await?;
+        let id = get_article_id(db, user, day)
+            .await?
+            .ok_or(ArticleError::NotFound)?;
+        return Ok(article::get(db, user, id).await?);
+    }
+    let email = super::user::email(db, user).await?.unwrap_or_default();
+    let date = day.format("%Y-%m-%d").to_string();
+    let title = day.format(&config.title).to_string();
+    let content = utils::fill_placeholders(
+        &config.template,
+        &[("date", &date), ("title", &title), ("user", &email)],
+    );
+    let insert = ArticleInsert {
+        id: None,
+        title: &title,
+        content,
+        tags: vec![DAILY_TAG],
+        language: config.language.as_deref(),
+        auto_tag: false,
+    };
+    let id = article::create(db, link_analyzer, events, user, insert).await?;
+    sqlx::query("UPDATE daily_notes SET article_id = $3 WHERE user_id = $1 AND day = $2")
+        .bind(user)
+        .bind(day)
+        .bind(id)
+        .execute(&mut tx)
+        .await?;
+    tx.commit().await?;
+    Ok(article::get(db, user, id).await?)
+}
+
+pub async fn calendar(
+    db: &Db,
+    user: i32,
+    (from, to): (NaiveDate, NaiveDate),
+) -> Result<Vec<DailyEntry>, DailyError> {
+    Ok(sqlx::query_as(
+        "SELECT day, article_id FROM daily_notes
+        WHERE user_id = $1 AND day BETWEEN $2 AND $3 AND article_id IS NOT NULL
+        ORDER BY day",
+    )
+    .bind(user)
+    .bind(from)
+    .bind(to)
+    .fetch_all(db)
+    .await?)
+}
+
+// ========================== HELPERS =====================
+
+async fn get_article_id(db: &Db, user: i32, day: NaiveDate) -> Result<Option<i32>, sqlx::Error> {
+    Ok(sqlx::query_scalar::<_, Option<i32>>(
+        "SELECT article_id FROM daily_notes WHERE user_id = $1 AND day = $2",
+    )
+    .bind(user)
+    .bind(day)
+    .fetch_optional(db)
+    .await?
+    .flatten())
+}
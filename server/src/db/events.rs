@@ -0,0 +1,107 @@
+use super::Db;
+use rocket::serde::Serialize;
+use rocket::tokio::sync::broadcast;
+use std::sync::Arc;
+
+// ========================== TYPES =======================
+
+const CHANNEL_CAPACITY: usize = 256;
+
+/// Event names in the order of the `EventKind` variants
+pub const EVENT_NAMES: &[&str] = &[
+    "article.created",
+    "article.updated",
+    "article.deleted",
+    "link.analyzed",
+];
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", content = "data")]
+pub enum EventKind {
+    #[serde(rename = "article.created")]
+    ArticleCreated { id: i32 },
+    #[serde(rename = "article.updated")]
+    ArticleUpdated { id: i32 },
+    #[serde(rename = "article.deleted")]
+    ArticleDeleted { id: i32 },
+    #[serde(rename = "link.analyzed")]
+    LinkAnalyzed {
+        id: i32,
+        article_id: i32,
+        url: String,
+        title: String,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct Event {
+    pub user: i32,
+    pub kind: EventKind,
+}
+
+/// In-process hub for change events.
+/// Webhook deliveries are persisted before subscribers are notified.
+pub struct Events {
+    db: Arc<Db>,
+    sender: broadcast::Sender<Event>,
+}
+
+impl EventKind {
+    pub fn name(&self) -> &'static str {
+        let index = match self {
+            EventKind::ArticleCreated { .. } => 0,
+            EventKind::ArticleUpdated { .. } => 1,
+            EventKind::ArticleDeleted { .. } => 2,
+            EventKind::LinkAnalyzed { .. } => 3,
+        };
+        EVENT_NAMES[index]
+    }
+}
+
+impl Events {
+    pub fn new(db: Arc<Db>) -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Events { db, sender }
+    }
+
+    /// Queues webhook deliveries and sends the event to all subscribers.
+    /// Subscribers may lag behind and miss events. The change is already
+    /// saved, so a failed enqueue is logged instead of failing the caller.
+    pub async fn emit(&self, user: i32, kind: EventKind) {
+        let event = Event { user, kind };
+        if let Err(e) = super::webhooks::enqueue(&self.db, &event).await {
+            log::warn!("Failed to queue webhook deliveries for {}: {:?}", event.kind.name(), e);
+        }
+        self.sender.send(event).ok();
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.sender.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::serde::json;
+
+    #[test]
+    fn names_match_serialized_tags() {
+        let kinds = [
+            EventKind::ArticleCreated { id: 1 },
+            EventKind::ArticleUpdated { id: 1 },
+            EventKind::ArticleDeleted { id: 1 },
+            EventKind::LinkAnalyzed {
+                id: 1,
+                article_id: 1,
+                url: String::new(),
+                title: String::new(),
+            },
+        ];
+        assert_eq!(kinds.len(), EVENT_NAMES.len());
+        for kind in &kinds {
+            let serialized = json::serde_json::to_string(kind).unwrap();
+            assert!(serialized.starts_with(&format!(r#"{{"event":"{}""#, kind.name())));
+        }
+    }
+}
@@ -1,4 +1,6 @@
+use super::events::{EventKind, Events};
 use super::Db;
+use sqlx::{Postgres, Transaction};
 use rocket::serde::{Deserialize, Serialize};
 use rocket::tokio::{
     self,
@@ -35,6 +37,13 @@ struct LinkUrl {
     url: String,
 }
 
+#[derive(Debug, sqlx::FromRow)]
+struct FreshLink {
+    id: i32,
+    user_id: i32,
+    url: String,
+}
+
 struct WebInfo {
     title: String,
     content: String,
@@ -129,16 +138,17 @@ pub async fn update_article_links(
     Ok(())
 }
 
-pub async fn delete_article_links(db: &Db, article: i32) 
+pub async fn delete_article_links(tx: &mut Transaction<'_, Postgres>, article: i32) 
 -> Result<(), sqlx::Error> {
     sqlx::query("DELETE FROM links WHERE article_id = $1")
         .bind(article)
-        .execute(db).await?;
+        .execute(&mut *tx).await?;
     Ok(())
 }
 
 pub async fn start_analyzer(
     db: Arc<Db>,
+    events: Arc<Events>,
     config: AnalyzerConfig,
 ) -> Result<Analyzer, WebDriverError> {
     log::info!("Starting link analyzer");
@@ -159,7 +169,7 @@ pub async fn start_analyzer(
     };
     let dbc = db.clone();
     tokio::spawn(async move {
-        analyze_links(rx, dbc, driver)
+        analyze_links(rx, dbc, events, driver)
             .await
             .expect("Link analyzer failed");
     });
@@ -179,6 +189,16 @@ pub async fn stop_analyzer(analyzer: Arc<Analyzer>) {
         .ok();
 }
 
+/// Analyzer that drops the links sent to it, for tests without a browser
+#[cfg(test)]
+pub fn test_analyzer(db: Arc<Db>) -> Analyzer {
+    let (sender, _) = mpsc::channel(1);
+    let process = std::process::Command::new("true")
+        .spawn()
+        .expect("Failed to spawn test process");
+    Analyzer { db, process, sender }
+}
+
 pub async fn analyze_fresh(analyzer: &Analyzer) -> Result<(), sqlx::Error> {
     let ids = get_fresh_links(analyzer.db.as_ref()).await?;
     log::info!("Sending {} fresh links to analyzer", ids.len());
@@ -191,6 +211,7 @@ pub async fn analyze_fresh(analyzer: &Analyzer) -> Result<(), sqlx::Error> {
 async fn analyze_links(
     mut recv: mpsc::Receiver<Message>,
     db: Arc<Db>,
+    events: Arc<Events>,
     driver: WebDriver,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let shutdown_sx = loop {
@@ -209,20 +230,29 @@ async fn analyze_links(
                 Ok(info) => info,
                 _ => continue,
             };
-            sqlx::query(
+            let updated = sqlx::query(
                 "UPDATE links SET fresh = FALSE, title = $2, content = $3, 
                 language = CAST($4 AS regconfig),
                 search_vector = to_tsvector(language, title || ' ' || content),
                 screenshot = $5
                 WHERE id = $1")
             .bind(link.id)
-            .bind(info.title)
+            .bind(&info.title)
             .bind(info.content)
             .bind(info.language)
             .bind(info.screenshot)
             .execute(db.as_ref())
-            .await
-            .ok();
+            .await;
+            if updated.is_ok() {
+                events
+                    .emit(link.user_id, EventKind::LinkAnalyzed {
+                        id: link.id,
+                        article_id: article,
+                        url: link.url,
+                        title: info.title,
+                    })
+                    .await;
+            }
         }
     };
     log::debug!("Closing webdriver");
@@ -249,9 +279,9 @@ async fn analyze_web_content(driver: &WebDriver, url: &str) -> WebDriverResult<W
     })
 }
 
-async fn get_link_urls(db: &Db, article: i32) -> Result<Vec<LinkUrl>, sqlx::Error> {
-    Ok(sqlx::query_as::<_, LinkUrl>(
-        "SELECT id, url FROM links WHERE article_id = $1 AND fresh = TRUE ")
+async fn get_link_urls(db: &Db, article: i32) -> Result<Vec<FreshLink>, sqlx::Error> {
+    Ok(sqlx::query_as::<_, FreshLink>(
+        "SELECT id, user_id, url FROM links WHERE article_id = $1 AND fresh = TRUE ")
     .bind(article)
     .fetch_all(db)
     .await?)
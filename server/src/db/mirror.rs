@@ -0,0 +1,352 @@
+use super::article::{self, ArticleInsert};
+use super::events::{Event, EventKind, Events};
+use super::links::Analyzer as LinkAnalyzer;
+use super::Db;
+use crate::utils::frontmatter;
+use notify::{DebouncedEvent, RecursiveMode, Watcher};
+use rocket::serde::Deserialize;
+use rocket::tokio::{
+    self, fs,
+    sync::{broadcast, broadcast::error::RecvError, mpsc},
+};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, Weak},
+    time::Duration,
+};
+
+// ========================== TYPES =======================
+
+const WATCH_DELAY: Duration = Duration::from_secs(1);
+
+type MirrorResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub enabled: bool,
+    /// Holds a directory per user
+    pub path: String,
+    /// Ids of the mirrored users, all users if empty
+    pub users: Vec<i32>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            enabled: false,
+            path: "mirror".to_owned(),
+            users: vec![],
+        }
+    }
+}
+
+/// Last synced state of a file
+#[derive(Debug, Clone)]
+struct FileState {
+    hash: blake3::Hash,
+    seq: i64,
+}
+
+struct Mirror {
+    db: Arc<Db>,
+    // weak, so the analyzer can be stopped on shutdown
+    link_analyzer: Weak<LinkAnalyzer>,
+    events: Arc<Events>,
+    root: PathBuf,
+    users: Vec<i32>,
+    files: Mutex<HashMap<PathBuf, FileState>>,
+}
+
+impl Mirror {
+    fn is_mirrored(&self, user: i32) -> bool {
+        self.users.is_empty() || self.users.contains(&user)
+    }
+}
+
+// ========================== FUNCTIONS ===================
+
+/// Exports all articles and keeps the directory in sync with the database
+pub async fn start_mirror(
+    db: Arc<Db>,
+    link_analyzer: &Arc<LinkAnalyzer>,
+    events: Arc<Events>,
+    config: Config,
+) -> MirrorResult<()> {
+    log::info!("Starting filesystem mirror in {}", config.path);
+    fs::create_dir_all(&config.path).await?;
+    // the watcher reports absolute paths
+    let root = fs::canonicalize(&config.path).await?;
+    let recv = events.subscribe();
+    let mirror = Arc::new(Mirror {
+        db,
+        link_analyzer: Arc::downgrade(link_analyzer),
+        events,
+        root,
+        users: config.users,
+        files: Mutex::new(HashMap::new()),
+    });
+    // initial export, into a directory per user
+    let users: Vec<i32> = sqlx::query_scalar("SELECT id FROM users ORDER BY id")
+        .fetch_all(mirror.db.as_ref())
+        .await?;
+    for user in users.into_iter().filter(|u| mirror.is_mirrored(*u)) {
+        fs::create_dir_all(mirror.root.join(user.to_string())).await?;
+        let articles: Vec<i32> =
+            sqlx::query_scalar("SELECT id FROM articles WHERE user_id = $1 ORDER BY id")
+                .bind(user)
+                .fetch_all(mirror.db.as_ref())
+                .await?;
+        for id in articles {
+            export(&mirror, user, id).await?;
+        }
+    }
+    // database -> files
+    let m = mirror.clone();
+    tokio::spawn(async move {
+        follow_events(m, recv).await;
+    });
+    // files -> database
+    let (sx, mut rx) = mpsc::unbounded_channel::<PathBuf>();
+    let watch_root = mirror.root.clone();
+    std::thread::spawn(move || {
+        let (wsx, wrx) = std::sync::mpsc::channel();
+        let mut watcher = notify::watcher(wsx, WATCH_DELAY).expect("Failed to create watcher");
+        watcher
+            .watch(&watch_root, RecursiveMode::Recursive)
+            .expect("Failed to watch mirror directory");
+        for event in wrx {
+            // editors saving atomically rename a temporary file over the article
+            let path = match event {
+                DebouncedEvent::Create(path)
+                | DebouncedEvent::Write(path)
+                | DebouncedEvent::Rename(_, path) => path,
+                _ => continue,
+            };
+            if sx.send(path).is_err() {
+                break;
+            }
+        }
+    });
+    tokio::spawn(async move {
+        while let Some(path) = rx.recv().await {
+            if let Err(e) = import(&mirror, &path).await {
+                log::warn!("Failed to import {:?}: {:?}", path, e);
+            }
+        }
+    });
+    Ok(())
+}
+
+// ========================== HELPERS =====================
+
+async fn follow_events(mirror: Arc<Mirror>, mut recv: broadcast::Receiver<Event>) {
+    loop {
+        let event = match recv.recv().await {
+            Ok(event) => event,
+            Err(RecvError::Lagged(skipped)) => {
+                log::warn!("Filesystem mirror skipped {} events", skipped);
+                continue;
+            }
+            Err(RecvError::Closed) => break,
+        };
+        if !mirror.is_mirrored(event.user) {
+            continue;
+        }
+        let result = match event.kind {
+            EventKind::ArticleCreated { id } | EventKind::ArticleUpdated { id } => {
+                export(&mirror, event.user, id).await
+            }
+            EventKind::ArticleDeleted { id } => remove(&mirror, event.user, id).await,
+            _ => Ok(()),
+        };
+        if let Err(e) = result {
+            log::warn!("Failed to mirror article: {:?}", e);
+        }
+    }
+}
+
+/// Writes the database version of the article.
+/// Unsynced external edits are kept in a conflict file.
+async fn export(mirror: &Mirror, user: i32, id: i32) -> MirrorResult<()> {
+    let article = article::get(&mirror.db, user, id).await?;
+    let seq = get_seq(&mirror.db, id).await?;
+    let path = article_path(&mirror.root, user, id);
+    let rendered =
+        frontmatter::render(article.id, &article.title, &article.tags, &article.content);
+    let hash = blake3::hash(rendered.as_bytes());
+    let known = mirror.files.lock().unwrap().get(&path).cloned();
+    match fs::read(&path).await {
+        Ok(current) => {
+            let current_hash = blake3::hash(&current);
+            if current_hash == hash {
+                mirror.files.lock().unwrap().insert(path, FileState { hash, seq });
+                return Ok(());
+            }
+            let edited = known.map(|k| k.hash != current_hash).unwrap_or(true);
+            if edited {
+                log::info!("Conflict in mirrored article {}", id);
+                fs::write(conflict_path(&path), &current).await?;
+            }
+        }
+        Err(_) => {
+            fs::create_dir_all(path.parent().expect("Article path has parent")).await?;
+        }
+    }
+    mirror
+        .files
+        .lock()
+        .unwrap()
+        .insert(path.clone(), FileState { hash, seq });
+    fs::write(&path, rendered).await?;
+    Ok(())
+}
+
+async fn remove(mirror: &Mirror, user: i32, id: i32) -> MirrorResult<()> {
+    let path = article_path(&mirror.root, user, id);
+    mirror.files.lock().unwrap().remove(&path);
+    if fs::metadata(&path).await.is_ok() {
+        fs::remove_file(&path).await?;
+    }
+    Ok(())
+}
+
+/// Pushes an external edit of the file to the database.
+/// Files that aren't mirrored articles are imported as new articles.
+async fn import(mirror: &Mirror, path: &Path) -> MirrorResult<()> {
+    let (user, name) = match parse_path(&mirror.root, path) {
+        Some((user, name)) if mirror.is_mirrored(user) => (user, name),
+        _ => return Ok(()),
+    };
+    let content = match fs::read(path).await {
+        Ok(content) => content,
+        // removed since the event, e.g. after an import
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    let hash = blake3::hash(&content);
+    let known = mirror.files.lock().unwrap().get(path).cloned();
+    let known = match known {
+        Some(known) => known,
+        None => return import_new(mirror, user, name, path, String::from_utf8(content)?).await,
+    };
+    // our own write
+    if known.hash == hash {
+        return Ok(());
+    }
+    let id: i32 = name.parse()?;
+    let file = match frontmatter::parse(&String::from_utf8(content)?) {
+        Some(file) if file.id == Some(id) => file,
+        _ => {
+            log::warn!("Malformed mirror file {:?}", path);
+            return Ok(());
+        }
+    };
+    // the database changed too, keep both versions
+    if get_seq(&mirror.db, id).await? != known.seq {
+        return export(mirror, user, id).await;
+    }
+    let insert = ArticleInsert {
+        id: Some(id),
+        title: &file.title,
+        content: file.content,
+        tags: file.tags.iter().map(|t| t.as_str()).collect(),
+        // keeps the stored override
+        language: None,
+        auto_tag: false,
+    };
+    let link_analyzer = match mirror.link_analyzer.upgrade() {
+        Some(link_analyzer) => link_analyzer,
+        None => return Ok(()),
+    };
+    // remember the edit so the update event doesn't flag it as a conflict
+    mirror
+        .files
+        .lock()
+        .unwrap()
+        .insert(path.to_owned(), FileState { hash, seq: known.seq });
+    article::update(
+        &mirror.db,
+        &link_analyzer,
+        &mirror.events,
+        user,
+        insert,
+        None,
+    )
+    .await?;
+    Ok(())
+}
+
+/// Creates an article from a new file. The file is removed, the export
+/// of the created article replaces it.
+async fn import_new(
+    mirror: &Mirror,
+    user: i32,
+    name: &str,
+    path: &Path,
+    content: String,
+) -> MirrorResult<()> {
+    // plain markdown is titled by the file name
+    let file = if content.starts_with("---\n") {
+        match frontmatter::parse(&content) {
+            Some(file) => file,
+            None => {
+                log::warn!("Malformed mirror file {:?}", path);
+                return Ok(());
+            }
+        }
+    } else {
+        frontmatter::Document {
+            id: None,
+            title: name.to_owned(),
+            tags: vec![],
+            content,
+        }
+    };
+    let insert = ArticleInsert {
+        id: None,
+        title: &file.title,
+        content: file.content,
+        tags: file.tags.iter().map(|t| t.as_str()).collect(),
+        language: None,
+        auto_tag: false,
+    };
+    let link_analyzer = match mirror.link_analyzer.upgrade() {
+        Some(link_analyzer) => link_analyzer,
+        None => return Ok(()),
+    };
+    let id = article::create(&mirror.db, &link_analyzer, &mirror.events, user, insert).await?;
+    log::info!("Imported {:?} as article {}", path, id);
+    fs::remove_file(path).await?;
+    Ok(())
+}
+
+async fn get_seq(db: &Db, id: i32) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar("SELECT seq FROM articles WHERE id = $1")
+        .bind(id)
+        .fetch_one(db)
+        .await
+}
+
+fn article_path(root: &Path, user: i32, id: i32) -> PathBuf {
+    root.join(user.to_string()).join(format!("{}.md", id))
+}
+
+fn conflict_path(path: &Path) -> PathBuf {
+    path.with_extension("conflict.md")
+}
+
+/// Extracts user and file name from `<root>/<user>/<name>.md`,
+/// articles are named by their id. Hidden and conflict files are skipped.
+fn parse_path<'a>(root: &Path, path: &'a Path) -> Option<(i32, &'a str)> {
+    let relative = path.strip_prefix(root).ok()?;
+    let mut parts = relative.iter();
+    let user = parts.next()?.to_str()?.parse().ok()?;
+    let file = parts.next()?.to_str()?;
+    if parts.next().is_some() || file.starts_with('.') || file.ends_with(".conflict.md") {
+        return None;
+    }
+    let name = file.strip_suffix(".md")?;
+    Some((user, name))
+}
@@ -2,9 +2,22 @@ use rocket::serde::Deserialize;
 use sqlx::ConnectOptions;
 
 pub mod article;
+pub mod crypto;
+pub mod daily;
+pub mod events;
 pub mod links;
+pub mod mirror;
+pub mod notebooks;
+pub mod review;
+pub mod revisions;
+pub mod searches;
+pub mod sync;
 pub mod tags;
+pub mod tasks;
+pub mod templates;
 pub mod user;
+pub mod versions;
+pub mod webhooks;
 // ========================== INIT ========================
 
 pub type Db = sqlx::PgPool;
@@ -48,3 +61,66 @@ pub async fn init(config: Config) -> Result<Db, sqlx::Error> {
 
     Ok(pool)
 }
+
+/// Names of the text search configs the database provides
+pub async fn text_search_configs(db: &Db) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar("SELECT cfgname::text FROM pg_ts_config")
+        .fetch_all(db)
+        .await
+}
+
+// ========================== TESTS =======================
+
+/// Migrated database of the tests from `TEST_DATABASE_URL`,
+/// tests that need postgres are skipped without it
+#[cfg(test)]
+pub async fn test_db() -> Option<Db> {
+    let url = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            eprintln!("TEST_DATABASE_URL is not set, skipping");
+            return None;
+        }
+    };
+    let pool = sqlx::postgres::PgPoolOptions::new()
+        .max_connections(4)
+        .connect(&url)
+        .await
+        .expect("Failed to connect to the test database");
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .expect("Failed to migrate the test database");
+    Some(pool)
+}
+
+/// Creates a user of its own for each test
+#[cfg(test)]
+pub async fn test_user(db: &Db) -> i32 {
+    sqlx::query_scalar(
+        "INSERT INTO users (email, password)
+        VALUES ('test-' || md5(random()::text) || '@localhost', '')
+        RETURNING id",
+    )
+    .fetch_one(db)
+    .await
+    .unwrap()
+}
+
+/// Creates an article with the tags, bypassing extraction and validation
+#[cfg(test)]
+pub async fn test_article(db: &Db, user: i32, tags: &[&str]) -> i32 {
+    let id = sqlx::query_scalar(
+        "INSERT INTO articles (user_id, title, content, raw_text, preview)
+        VALUES ($1, 'Test', 'test', 'test', 'test')
+        RETURNING id",
+    )
+    .bind(user)
+    .fetch_one(db)
+    .await
+    .unwrap();
+    let mut tx = db.begin().await.unwrap();
+    tags::update_article_tags(&mut tx, user, id, tags).await.unwrap();
+    tx.commit().await.unwrap();
+    id
+}
@@ -0,0 +1,188 @@
+use super::Db;
+use rocket::serde::{Deserialize, Serialize};
+
+// ========================== TYPES =======================
+
+#[derive(Debug, sqlx::FromRow, Serialize)]
+pub struct Notebook {
+    pub id: i32,
+    pub parent_id: Option<i32>,
+    pub name: String,
+    pub num_articles: i64,
+}
+
+#[derive(Deserialize)]
+pub struct NotebookInsert<'a> {
+    pub name: &'a str,
+    pub parent_id: Option<i32>,
+}
+
+// ========================== ERRORS ======================
+
+#[derive(Debug, thiserror::Error, Serialize)]
+pub enum NotebookError {
+    #[error("Not found")]
+    NotFound,
+    #[error("Bad content")]
+    BadContent,
+    #[error("Bad parent")]
+    BadParent,
+    #[error("Internal")]
+    Internal(
+        #[from]
+        #[source]
+        #[serde(skip)]
+        sqlx::Error,
+    ),
+}
+
+// ========================== FUNCTIONS ===================
+
+pub async fn list(db: &Db, user: i32) -> Result<Vec<Notebook>, NotebookError> {
+    Ok(sqlx::query_as(
+        "SELECT n.id, n.parent_id, n.name,
+        (SELECT COUNT(id) FROM articles a WHERE a.notebook_id = n.id) as num_articles
+        FROM notebooks n
+        WHERE n.user_id = $1
+        ORDER BY n.name",
+    )
+    .bind(user)
+    .fetch_all(db)
+    .await?)
+}
+
+pub async fn create(
+    db: &Db,
+    user: i32,
+    notebook: NotebookInsert<'_>,
+) -> Result<i32, NotebookError> {
+    if notebook.name.trim().is_empty() {
+        return Err(NotebookError::BadContent);
+    }
+    if let Some(parent) = notebook.parent_id {
+        check_access(db, user, parent)
+            .await
+            .map_err(|_| NotebookError::BadParent)?;
+    }
+    Ok(sqlx::query_scalar(
+        "INSERT INTO notebooks (user_id, parent_id, name) VALUES ($1, $2, $3)
+        RETURNING id",
+    )
+    .bind(user)
+    .bind(notebook.parent_id)
+    .bind(notebook.name.trim())
+    .fetch_one(db)
+    .await?)
+}
+
+pub async fn rename(db: &Db, user: i32, id: i32, name: &str) -> Result<(), NotebookError> {
+    if name.trim().is_empty() {
+        return Err(NotebookError::BadContent);
+    }
+    check_access(db, user, id).await?;
+    sqlx::query("UPDATE notebooks SET name = $2 WHERE id = $1")
+        .bind(id)
+        .bind(name.trim())
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+pub async fn move_notebook(
+    db: &Db,
+    user: i32,
+    id: i32,
+    parent: Option<i32>,
+) -> Result<(), NotebookError> {
+    check_access(db, user, id).await?;
+    if let Some(parent) = parent {
+        check_access(db, user, parent)
+            .await
+            .map_err(|_| NotebookError::BadParent)?;
+        // the new parent can't be inside the moved notebook
+        let cycle: bool = sqlx::query_scalar(
+            "
+            WITH RECURSIVE sub AS (
+                SELECT id FROM notebooks WHERE id = $1
+                UNION ALL
+                SELECT n.id FROM notebooks n JOIN sub ON n.parent_id = sub.id
+            )
+            SELECT EXISTS(SELECT 1 FROM sub WHERE id = $2)",
+        )
+        .bind(id)
+        .bind(parent)
+        .fetch_one(db)
+        .await?;
+        if cycle {
+            return Err(NotebookError::BadParent);
+        }
+    }
+    sqlx::query("UPDATE notebooks SET parent_id = $2 WHERE id = $1")
+        .bind(id)
+        .bind(parent)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+/// Deletes the notebook, its children and articles move up to its parent
+pub async fn delete(db: &Db, user: i32, id: i32) -> Result<(), NotebookError> {
+    check_access(db, user, id).await?;
+    let mut tx = db.begin().await?;
+    let parent: Option<i32> = sqlx::query_scalar("SELECT parent_id FROM notebooks WHERE id = $1")
+        .bind(id)
+        .fetch_one(&mut tx)
+        .await?;
+    sqlx::query("UPDATE notebooks SET parent_id = $2 WHERE parent_id = $1")
+        .bind(id)
+        .bind(parent)
+        .execute(&mut tx)
+        .await?;
+    sqlx::query("UPDATE articles SET notebook_id = $2 WHERE notebook_id = $1")
+        .bind(id)
+        .bind(parent)
+        .execute(&mut tx)
+        .await?;
+    sqlx::query("DELETE FROM notebooks WHERE id = $1")
+        .bind(id)
+        .execute(&mut tx)
+        .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+pub async fn move_article(
+    db: &Db,
+    user: i32,
+    article: i32,
+    notebook: Option<i32>,
+) -> Result<(), NotebookError> {
+    if let Some(notebook) = notebook {
+        check_access(db, user, notebook)
+            .await
+            .map_err(|_| NotebookError::BadParent)?;
+    }
+    let result = sqlx::query("UPDATE articles SET notebook_id = $3 WHERE id = $1 AND user_id = $2")
+        .bind(article)
+        .bind(user)
+        .bind(notebook)
+        .execute(db)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(NotebookError::NotFound);
+    }
+    Ok(())
+}
+
+// ========================== HELPERS =====================
+
+async fn check_access(db: &Db, user: i32, id: i32) -> Result<(), NotebookError> {
+    let stored_user: Option<i32> = sqlx::query_scalar("SELECT user_id FROM notebooks WHERE id = $1")
+        .bind(id)
+        .fetch_optional(db)
+        .await?;
+    match stored_user {
+        Some(stored_user) if stored_user == user => Ok(()),
+        _ => Err(NotebookError::NotFound),
+    }
+}
@@ -0,0 +1,210 @@
+use super::article::{self, ArticlePreview};
+use super::Db;
+use chrono::{Duration, Local, NaiveDate};
+use rocket::serde::Serialize;
+
+// ========================== TYPES =======================
+
+#[derive(Debug, Clone, sqlx::FromRow, Serialize)]
+pub struct Review {
+    pub article_id: i32,
+    pub repetitions: i32,
+    pub interval_days: i32,
+    pub ease: f64,
+    pub due_on: NaiveDate,
+    pub reviewed_on: Option<NaiveDate>,
+}
+
+const MIN_EASE: f64 = 1.3;
+const MAX_QUALITY: i32 = 5;
+
+// ========================== ERRORS ======================
+
+#[derive(Debug, thiserror::Error, Serialize)]
+pub enum ReviewError {
+    #[error("Not found")]
+    NotFound,
+    #[error("Bad grade")]
+    BadGrade,
+    #[error("Internal")]
+    Internal(
+        #[from]
+        #[source]
+        #[serde(skip)]
+        sqlx::Error,
+    ),
+}
+
+// ========================== FUNCTIONS ===================
+
+/// Opts the article into review, it becomes due today
+pub async fn enable(db: &Db, user: i32, article: i32) -> Result<Review, ReviewError> {
+    let review = sqlx::query_as::<_, Review>(
+        "
+        INSERT INTO reviews (article_id, user_id)
+        SELECT id, user_id FROM articles WHERE id = $1 AND user_id = $2
+        ON CONFLICT (article_id) DO UPDATE SET article_id = EXCLUDED.article_id
+        RETURNING article_id, repetitions, interval_days, ease, due_on, reviewed_on",
+    )
+    .bind(article)
+    .bind(user)
+    .fetch_optional(db)
+    .await?;
+    review.ok_or(ReviewError::NotFound)
+}
+
+pub async fn disable(db: &Db, user: i32, article: i32) -> Result<(), ReviewError> {
+    let result = sqlx::query("DELETE FROM reviews WHERE article_id = $1 AND user_id = $2")
+        .bind(article)
+        .bind(user)
+        .execute(db)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(ReviewError::NotFound);
+    }
+    Ok(())
+}
+
+pub async fn due(db: &Db, user: i32, limit: u32) -> Result<Vec<ArticlePreview>, ReviewError> {
+    let due = sqlx::query_as(
+        "
+        SELECT a.id, a.title, a.preview, a.notebook_id, a.created_on, a.updated_on,
+            ARRAY_REMOVE(ARRAY_AGG(t.name), NULL) as tags
+        FROM reviews r
+            JOIN articles a ON a.id = r.article_id
+            LEFT JOIN article_tags at ON at.article_id = a.id
+            LEFT JOIN tags t ON at.tag_id = t.id
+        WHERE r.user_id = $1 AND r.due_on <= $2
+        GROUP BY a.id, r.due_on
+        ORDER BY r.due_on, a.id
+        LIMIT $3",
+    )
+    .bind(user)
+    .bind(today())
+    .bind(limit)
+    .fetch_all(db)
+    .await?;
+    Ok(article::decrypt_previews(db, user, due).await?)
+}
+
+/// Records the recall quality (0-5) and schedules the next review
+pub async fn grade(db: &Db, user: i32, article: i32, quality: i32) -> Result<Review, ReviewError> {
+    if !(0..=MAX_QUALITY).contains(&quality) {
+        return Err(ReviewError::BadGrade);
+    }
+    let review = sqlx::query_as::<_, Review>(
+        "
+        SELECT article_id, repetitions, interval_days, ease, due_on, reviewed_on
+        FROM reviews WHERE article_id = $1 AND user_id = $2",
+    )
+    .bind(article)
+    .bind(user)
+    .fetch_optional(db)
+    .await?
+    .ok_or(ReviewError::NotFound)?;
+    let next = schedule(review, quality, today());
+    sqlx::query(
+        "
+        UPDATE reviews SET
+        repetitions = $2, interval_days = $3, ease = $4, due_on = $5, reviewed_on = $6
+        WHERE article_id = $1",
+    )
+    .bind(next.article_id)
+    .bind(next.repetitions)
+    .bind(next.interval_days)
+    .bind(next.ease)
+    .bind(next.due_on)
+    .bind(next.reviewed_on)
+    .execute(db)
+    .await?;
+    Ok(next)
+}
+
+// ========================== HELPERS =====================
+
+fn today() -> NaiveDate {
+    Local::now().naive_local().date()
+}
+
+/// SM-2 scheduling step
+fn schedule(review: Review, quality: i32, today: NaiveDate) -> Review {
+    let (repetitions, interval_days) = if quality < 3 {
+        (0, 1)
+    } else {
+        let interval_days = match review.repetitions {
+            0 => 1,
+            1 => 6,
+            _ => (review.interval_days as f64 * review.ease).round() as i32,
+        };
+        (review.repetitions + 1, interval_days)
+    };
+    let penalty = (MAX_QUALITY - quality) as f64;
+    let ease = (review.ease + 0.1 - penalty * (0.08 + penalty * 0.02)).max(MIN_EASE);
+    Review {
+        repetitions,
+        interval_days,
+        ease,
+        due_on: today + Duration::days(interval_days as i64),
+        reviewed_on: Some(today),
+        ..review
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_review(today: NaiveDate) -> Review {
+        Review {
+            article_id: 1,
+            repetitions: 0,
+            interval_days: 0,
+            ease: 2.5,
+            due_on: today,
+            reviewed_on: None,
+        }
+    }
+
+    #[test]
+    fn intervals_grow_with_correct_answers() {
+        let today = NaiveDate::from_ymd_opt(2021, 10, 1).unwrap();
+        let review = schedule(new_review(today), 5, today);
+        assert_eq!((review.repetitions, review.interval_days), (1, 1));
+        assert!((review.ease - 2.6).abs() < 1e-9);
+        assert_eq!(review.due_on, NaiveDate::from_ymd_opt(2021, 10, 2).unwrap());
+        assert_eq!(review.reviewed_on, Some(today));
+
+        let review = schedule(review, 4, today);
+        assert_eq!((review.repetitions, review.interval_days), (2, 6));
+        assert!((review.ease - 2.6).abs() < 1e-9);
+
+        let review = schedule(review, 3, today);
+        assert_eq!((review.repetitions, review.interval_days), (3, 16));
+        assert!((review.ease - 2.46).abs() < 1e-9);
+        assert_eq!(review.due_on, NaiveDate::from_ymd_opt(2021, 10, 17).unwrap());
+    }
+
+    #[test]
+    fn wrong_answer_resets_repetitions() {
+        let today = NaiveDate::from_ymd_opt(2021, 10, 1).unwrap();
+        let review = Review {
+            repetitions: 4,
+            interval_days: 30,
+            ease: 2.6,
+            ..new_review(today)
+        };
+        let review = schedule(review, 1, today);
+        assert_eq!((review.repetitions, review.interval_days), (0, 1));
+        assert!((review.ease - 2.06).abs() < 1e-9);
+    }
+
+    #[test]
+    fn ease_has_a_minimum() {
+        let today = NaiveDate::from_ymd_opt(2021, 10, 1).unwrap();
+        let review = Review {
+            ease: MIN_EASE,
+            ..new_review(today)
+        };
+        assert!((schedule(review, 0, today).ease - MIN_EASE).abs() < 1e-9);
+    }
+}
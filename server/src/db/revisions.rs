@@ -0,0 +1,248 @@
+use super::article::{self, ArticleError};
+use super::crypto;
+use super::Db;
+use crate::utils::diff;
+use chrono::NaiveDateTime;
+use rocket::serde::Serialize;
+use sqlx::{Postgres, Transaction};
+use std::collections::HashSet;
+
+// ========================== TYPES =======================
+
+/// Saves within the window replace the latest revision
+const COALESCE_MINUTES: i32 = 10;
+/// Older revisions of the article are pruned
+const MAX_REVISIONS: i64 = 100;
+
+#[derive(Debug, sqlx::FromRow, Serialize)]
+pub struct Revision {
+    pub id: i32,
+    pub title: String,
+    pub created_on: NaiveDateTime,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct Snapshot {
+    title: String,
+    content: String,
+    tags: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TitleChange {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TagChanges {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Diff {
+    pub title: Option<TitleChange>,
+    pub tags: TagChanges,
+    pub hunks: Vec<diff::Hunk>,
+}
+
+// ========================== ERRORS ======================
+
+#[derive(Debug, thiserror::Error, Serialize)]
+pub enum RevisionError {
+    #[error("Revision not found")]
+    RevisionNotFound,
+    #[error(transparent)]
+    Article(#[from] ArticleError),
+    #[error("Internal")]
+    Internal(
+        #[from]
+        #[source]
+        #[serde(skip)]
+        sqlx::Error,
+    ),
+}
+
+// ========================== FUNCTIONS ===================
+
+pub async fn list(db: &Db, user: i32, article: i32) -> Result<Vec<Revision>, RevisionError> {
+    // check access
+    article::get(db, user, article).await?;
+    Ok(sqlx::query_as(
+        "SELECT id, title, created_on FROM article_revisions
+        WHERE article_id = $1
+        ORDER BY id DESC",
+    )
+    .bind(article)
+    .fetch_all(db)
+    .await?)
+}
+
+/// Diffs two revisions of the article, `to` defaults to the current version
+pub async fn diff(
+    db: &Db,
+    user: i32,
+    article: i32,
+    from: i32,
+    to: Option<i32>,
+) -> Result<Diff, RevisionError> {
+    let current = article::get(db, user, article).await?;
+    let from = get_snapshot(db, user, article, from).await?;
+    let to = match to {
+        Some(to) => get_snapshot(db, user, article, to).await?,
+        None => Snapshot {
+            title: current.title,
+            content: current.content,
+            tags: current.tags,
+        },
+    };
+    let title = if from.title != to.title {
+        Some(TitleChange {
+            from: from.title,
+            to: to.title,
+        })
+    } else {
+        None
+    };
+    let (from_tags, to_tags): (HashSet<_>, HashSet<_>) =
+        (from.tags.iter().collect(), to.tags.iter().collect());
+    let sorted = |tags: Vec<&&String>| {
+        let mut tags: Vec<String> = tags.into_iter().map(|t| t.to_string()).collect();
+        tags.sort();
+        tags
+    };
+    let tags = TagChanges {
+        added: sorted(to_tags.difference(&from_tags).collect()),
+        removed: sorted(from_tags.difference(&to_tags).collect()),
+    };
+    Ok(Diff {
+        title,
+        tags,
+        hunks: diff::diff_lines(&from.content, &to.content),
+    })
+}
+
+/// Stores the saved version, coalescing saves in quick succession.
+/// The pool only provides the encryption key.
+pub async fn record(
+    db: &Db,
+    tx: &mut Transaction<'_, Postgres>,
+    user: i32,
+    article: i32,
+    title: &str,
+    content: &str,
+    tags: &[&str],
+) -> Result<(), sqlx::Error> {
+    let tags: Vec<String> = tags.iter().map(|t| t.to_lowercase()).collect();
+    let content = crypto::encrypt(db, user, content).await?;
+    let coalesced = sqlx::query(
+        "UPDATE article_revisions SET title = $2, content = $3, tags = $4
+        WHERE id = (SELECT MAX(id) FROM article_revisions WHERE article_id = $1)
+            AND created_on > LOCALTIMESTAMP - make_interval(mins => $5)",
+    )
+    .bind(article)
+    .bind(title)
+    .bind(&content)
+    .bind(&tags)
+    .bind(COALESCE_MINUTES)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    if coalesced > 0 {
+        return Ok(());
+    }
+    sqlx::query(
+        "INSERT INTO article_revisions (article_id, title, content, tags)
+        VALUES ($1, $2, $3, $4)",
+    )
+    .bind(article)
+    .bind(title)
+    .bind(content)
+    .bind(tags)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query(
+        "DELETE FROM article_revisions
+        WHERE article_id = $1 AND id NOT IN (
+            SELECT id FROM article_revisions WHERE article_id = $1
+            ORDER BY id DESC LIMIT $2
+        )",
+    )
+    .bind(article)
+    .bind(MAX_REVISIONS)
+    .execute(&mut *tx)
+    .await?;
+    Ok(())
+}
+
+// ========================== HELPERS =====================
+
+async fn get_snapshot(
+    db: &Db,
+    user: i32,
+    article: i32,
+    id: i32,
+) -> Result<Snapshot, RevisionError> {
+    let mut snapshot = sqlx::query_as::<_, Snapshot>(
+        "SELECT title, content, tags FROM article_revisions
+        WHERE id = $1 AND article_id = $2",
+    )
+    .bind(id)
+    .bind(article)
+    .fetch_optional(db)
+    .await?
+    .ok_or(RevisionError::RevisionNotFound)?;
+    snapshot.content = crypto::decrypt(db, user, snapshot.content).await?;
+    Ok(snapshot)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{test_article, test_db, test_user};
+
+    async fn save(db: &Db, user: i32, article: i32, title: &str, content: &str, tags: &[&str]) {
+        let mut tx = db.begin().await.unwrap();
+        record(db, &mut tx, user, article, title, content, tags).await.unwrap();
+        tx.commit().await.unwrap();
+    }
+
+    #[rocket::async_test]
+    async fn quick_saves_are_coalesced() {
+        let db = match test_db().await {
+            Some(db) => db,
+            None => return,
+        };
+        let user = test_user(&db).await;
+        let article = test_article(&db, user, &["rust"]).await;
+        save(&db, user, article, "First", "one\ntwo", &["rust"]).await;
+        save(&db, user, article, "Second", "one\nthree", &["Rust", "go"]).await;
+        let revisions = list(&db, user, article).await.unwrap();
+        assert_eq!(revisions.len(), 1);
+        assert_eq!(revisions[0].title, "Second");
+
+        // leave the coalescing window
+        sqlx::query(
+            "UPDATE article_revisions SET created_on = created_on - interval '1 hour'
+            WHERE article_id = $1",
+        )
+        .bind(article)
+        .execute(&db)
+        .await
+        .unwrap();
+        save(&db, user, article, "Third", "one\nfour", &["go"]).await;
+        let revisions = list(&db, user, article).await.unwrap();
+        let titles: Vec<&str> = revisions.iter().map(|r| r.title.as_str()).collect();
+        assert_eq!(titles, vec!["Third", "Second"]);
+
+        let diff = diff(&db, user, article, revisions[1].id, Some(revisions[0].id))
+            .await
+            .unwrap();
+        let title = diff.title.unwrap();
+        assert_eq!((title.from.as_str(), title.to.as_str()), ("Second", "Third"));
+        assert!(diff.tags.added.is_empty());
+        assert_eq!(diff.tags.removed, vec!["rust"]);
+        assert_eq!(diff.hunks.len(), 1);
+    }
+}
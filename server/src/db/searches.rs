@@ -0,0 +1,212 @@
+use super::article::{self, ArticleError, ArticlePreview, ListOptions};
+use super::Db;
+use rocket::serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// ========================== TYPES =======================
+
+#[derive(Debug, sqlx::FromRow, Serialize)]
+pub struct SavedSearch {
+    pub id: i32,
+    pub name: String,
+    pub query: String,
+    pub tags: Vec<i32>,
+    pub all_tags: bool,
+    pub exclude_tags: Vec<i32>,
+    pub sort_by_created: bool,
+    pub notebook_id: Option<i32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SavedSearchCount {
+    #[serde(flatten)]
+    pub search: SavedSearch,
+    pub count: i64,
+}
+
+#[derive(Deserialize)]
+pub struct SavedSearchInsert<'a> {
+    pub id: Option<i32>,
+    pub name: &'a str,
+    #[serde(default)]
+    pub query: String,
+    #[serde(default)]
+    pub tags: Vec<i32>,
+    #[serde(default)]
+    pub all_tags: bool,
+    #[serde(default)]
+    pub exclude_tags: Vec<i32>,
+    #[serde(default)]
+    pub sort_by_created: bool,
+    pub notebook_id: Option<i32>,
+}
+
+// ========================== ERRORS ======================
+
+#[derive(Debug, thiserror::Error, Serialize)]
+pub enum SearchError {
+    #[error("Not found")]
+    NotFound,
+    #[error("Bad content")]
+    BadContent,
+    #[error(transparent)]
+    Article(#[from] ArticleError),
+    #[error("Internal")]
+    Internal(
+        #[from]
+        #[source]
+        #[serde(skip)]
+        sqlx::Error,
+    ),
+}
+
+impl SavedSearch {
+    fn list_options(&self, (offset, limit): (u32, u32)) -> ListOptions {
+        ListOptions {
+            offset,
+            limit,
+            tags: self.tags.clone(),
+            all_tags: self.all_tags,
+            exclude_tags: self.exclude_tags.clone(),
+            sort_by_created: self.sort_by_created,
+            query: self.query.clone(),
+            notebook: self.notebook_id,
+        }
+    }
+}
+
+// ========================== FUNCTIONS ===================
+
+pub async fn list(db: &Db, user: i32) -> Result<Vec<SavedSearchCount>, SearchError> {
+    let searches = sqlx::query_as::<_, SavedSearch>(
+        "SELECT id, name, query, tags, all_tags, exclude_tags, sort_by_created, notebook_id
+        FROM saved_searches
+        WHERE user_id = $1
+        ORDER BY name",
+    )
+    .bind(user)
+    .fetch_all(db)
+    .await?;
+    let mut counts = count_all(db, user).await?;
+    Ok(searches
+        .into_iter()
+        .map(|search| SavedSearchCount {
+            count: counts.remove(&search.id).unwrap_or(0),
+            search,
+        })
+        .collect())
+}
+
+pub async fn get(db: &Db, user: i32, id: i32) -> Result<SavedSearch, SearchError> {
+    sqlx::query_as::<_, SavedSearch>(
+        "SELECT id, name, query, tags, all_tags, exclude_tags, sort_by_created, notebook_id
+        FROM saved_searches
+        WHERE id = $1 AND user_id = $2",
+    )
+    .bind(id)
+    .bind(user)
+    .fetch_optional(db)
+    .await?
+    .ok_or(SearchError::NotFound)
+}
+
+pub async fn articles(
+    db: &Db,
+    user: i32,
+    id: i32,
+    page: (u32, u32),
+) -> Result<Vec<ArticlePreview>, SearchError> {
+    let search = get(db, user, id).await?;
+    Ok(article::list(db, user, search.list_options(page)).await?)
+}
+
+pub async fn create(
+    db: &Db,
+    user: i32,
+    search: SavedSearchInsert<'_>,
+) -> Result<i32, SearchError> {
+    if search.name.trim().is_empty() {
+        return Err(SearchError::BadContent);
+    }
+    Ok(sqlx::query_scalar(
+        "
+        INSERT INTO saved_searches
+            (user_id, name, query, tags, all_tags, sort_by_created, notebook_id, exclude_tags)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        RETURNING id",
+    )
+    .bind(user)
+    .bind(search.name.trim())
+    .bind(search.query)
+    .bind(search.tags)
+    .bind(search.all_tags)
+    .bind(search.sort_by_created)
+    .bind(search.notebook_id)
+    .bind(search.exclude_tags)
+    .fetch_one(db)
+    .await?)
+}
+
+pub async fn update(db: &Db, user: i32, search: SavedSearchInsert<'_>) -> Result<(), SearchError> {
+    let id = search.id.ok_or(SearchError::BadContent)?;
+    if search.name.trim().is_empty() {
+        return Err(SearchError::BadContent);
+    }
+    let result = sqlx::query(
+        "
+        UPDATE saved_searches SET
+        name = $3, query = $4, tags = $5, all_tags = $6, sort_by_created = $7, notebook_id = $8,
+        exclude_tags = $9
+        WHERE id = $1 AND user_id = $2",
+    )
+    .bind(id)
+    .bind(user)
+    .bind(search.name.trim())
+    .bind(search.query)
+    .bind(search.tags)
+    .bind(search.all_tags)
+    .bind(search.sort_by_created)
+    .bind(search.notebook_id)
+    .bind(search.exclude_tags)
+    .execute(db)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(SearchError::NotFound);
+    }
+    Ok(())
+}
+
+pub async fn delete(db: &Db, user: i32, id: i32) -> Result<(), SearchError> {
+    let result = sqlx::query("DELETE FROM saved_searches WHERE id = $1 AND user_id = $2")
+        .bind(id)
+        .bind(user)
+        .execute(db)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(SearchError::NotFound);
+    }
+    Ok(())
+}
+
+// ========================== HELPERS =====================
+
+/// Result counts of all saved searches of the user in one query
+async fn count_all(db: &Db, user: i32) -> Result<HashMap<i32, i64>, sqlx::Error> {
+    let counts: Vec<(i32, i64)> = sqlx::query_as(concatcp!(
+        "WITH filters AS (
+            SELECT id as key, user_id, query, notebook_id as notebook, exclude_tags,
+                CASE WHEN all_tags THEN '{}' ELSE tags END as or_tags,
+                CASE WHEN all_tags THEN tags ELSE '{}' END as all_tags
+            FROM saved_searches
+            WHERE user_id = $1
+        )
+        SELECT key, COUNT(*) FROM (SELECT f.key, a.id",
+        article::LIST_FILTER,
+        ") matches
+        GROUP BY key",
+    ))
+    .bind(user)
+    .fetch_all(db)
+    .await?;
+    Ok(counts.into_iter().collect())
+}
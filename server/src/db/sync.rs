@@ -0,0 +1,327 @@
+use super::article::{self, ArticleError, ArticleInsert};
+use super::crypto;
+use super::events::Events;
+use super::links::Analyzer as LinkAnalyzer;
+use super::Db;
+use chrono::NaiveDateTime;
+use rocket::serde::{Deserialize, Serialize};
+
+// ========================== TYPES =======================
+
+#[derive(Debug, sqlx::FromRow, Serialize)]
+pub struct SyncArticle {
+    pub id: i32,
+    pub title: String,
+    pub content: String,
+    pub tags: Vec<String>,
+    pub notebook_id: Option<i32>,
+    pub created_on: NaiveDateTime,
+    pub updated_on: NaiveDateTime,
+    pub seq: i64,
+}
+
+#[derive(Debug, sqlx::FromRow, Serialize)]
+pub struct SyncTag {
+    pub id: i32,
+    pub name: String,
+    pub seq: i64,
+}
+
+#[derive(Debug, sqlx::FromRow, Serialize)]
+pub struct SyncLink {
+    pub id: i32,
+    pub article_id: i32,
+    pub url: String,
+    pub title: Option<String>,
+    pub seq: i64,
+}
+
+#[derive(Debug, sqlx::FromRow, Serialize)]
+pub struct Tombstone {
+    pub kind: String,
+    pub record_id: i32,
+    pub seq: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Changes {
+    /// Cursor to pass as `since` in the next sync, a transaction id watermark.
+    /// It isn't comparable to the `seq` of records. Changes of transactions
+    /// that were still running are sent again with it.
+    pub cursor: i64,
+    pub articles: Vec<SyncArticle>,
+    pub tags: Vec<SyncTag>,
+    pub links: Vec<SyncLink>,
+    pub deleted: Vec<Tombstone>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PushChange {
+    /// Client reference, echoed back in the result
+    pub client_id: Option<String>,
+    /// Missing for new articles
+    pub id: Option<i32>,
+    /// `seq` of the article record the change is based on, not the sync cursor
+    pub base_seq: Option<i64>,
+    #[serde(default)]
+    pub deleted: bool,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub content: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PushStatus {
+    Applied,
+    Conflict,
+    Rejected,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PushResult {
+    pub client_id: Option<String>,
+    pub id: Option<i32>,
+    pub status: PushStatus,
+    pub seq: Option<i64>,
+    pub error: Option<String>,
+}
+
+// ========================== ERRORS ======================
+
+#[derive(Debug, thiserror::Error, Serialize)]
+pub enum SyncError {
+    #[error("Internal")]
+    Internal(
+        #[from]
+        #[source]
+        #[serde(skip)]
+        sqlx::Error,
+    ),
+}
+
+// ========================== FUNCTIONS ===================
+
+/// Returns all records changed or deleted since the cursor of the previous sync,
+/// read from one snapshot
+pub async fn changes(db: &Db, user: i32, since: i64) -> Result<Changes, SyncError> {
+    let mut tx = db.begin().await?;
+    sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+        .execute(&mut tx)
+        .await?;
+    // transactions below the xmin of the snapshot are finished
+    let cursor: i64 = sqlx::query_scalar("SELECT txid_snapshot_xmin(txid_current_snapshot())")
+        .fetch_one(&mut tx)
+        .await?;
+    let mut articles: Vec<SyncArticle> = sqlx::query_as(
+        "
+        SELECT a.id, a.title, a.content, a.notebook_id, a.created_on, a.updated_on, a.seq,
+            ARRAY_REMOVE(ARRAY_AGG(t.name), NULL) as tags
+        FROM articles a
+            LEFT JOIN article_tags at ON at.article_id = a.id
+            LEFT JOIN tags t ON at.tag_id = t.id
+        WHERE a.user_id = $1 AND a.txid >= $2
+        GROUP BY a.id
+        ORDER BY a.seq",
+    )
+    .bind(user)
+    .bind(since)
+    .fetch_all(&mut tx)
+    .await?;
+    let tags: Vec<SyncTag> = sqlx::query_as(
+        "SELECT id, name, seq FROM tags WHERE user_id = $1 AND txid >= $2 ORDER BY seq",
+    )
+    .bind(user)
+    .bind(since)
+    .fetch_all(&mut tx)
+    .await?;
+    let links: Vec<SyncLink> = sqlx::query_as(
+        "SELECT id, article_id, url, title, seq FROM links
+        WHERE user_id = $1 AND txid >= $2
+        ORDER BY seq",
+    )
+    .bind(user)
+    .bind(since)
+    .fetch_all(&mut tx)
+    .await?;
+    let deleted: Vec<Tombstone> = sqlx::query_as(
+        "SELECT kind, record_id, seq FROM tombstones
+        WHERE user_id = $1 AND txid >= $2
+        ORDER BY seq",
+    )
+    .bind(user)
+    .bind(since)
+    .fetch_all(&mut tx)
+    .await?;
+    tx.commit().await?;
+    for article in &mut articles {
+        article.content = crypto::decrypt(db, user, std::mem::take(&mut article.content)).await?;
+    }
+    Ok(Changes {
+        cursor,
+        articles,
+        tags,
+        links,
+        deleted,
+    })
+}
+
+/// Applies client changes in order. Changes based on an outdated seq are reported as conflicts.
+pub async fn push(
+    db: &Db,
+    link_analyzer: &LinkAnalyzer,
+    events: &Events,
+    user: i32,
+    changes: Vec<PushChange>,
+) -> Result<Vec<PushResult>, SyncError> {
+    let mut results = Vec::with_capacity(changes.len());
+    for change in changes {
+        let result = apply(db, link_analyzer, events, user, &change).await;
+        let (id, status, error) = match result {
+            Ok(id) => (Some(id), PushStatus::Applied, None),
+            Err(ApplyError::Conflict) => (change.id, PushStatus::Conflict, None),
+            Err(ApplyError::Article(ArticleError::Internal(e))) => return Err(e.into()),
+            Err(ApplyError::Article(e)) => (change.id, PushStatus::Rejected, Some(e.to_string())),
+            Err(ApplyError::Internal(e)) => return Err(e.into()),
+        };
+        let seq = match (&status, id) {
+            (PushStatus::Applied, _) if change.deleted => None,
+            (_, Some(id)) => get_seq(db, user, id).await?,
+            _ => None,
+        };
+        results.push(PushResult {
+            client_id: change.client_id,
+            id,
+            status,
+            seq,
+            error,
+        });
+    }
+    Ok(results)
+}
+
+// ========================== HELPERS =====================
+
+enum ApplyError {
+    Conflict,
+    Article(ArticleError),
+    Internal(sqlx::Error),
+}
+
+impl From<ArticleError> for ApplyError {
+    fn from(e: ArticleError) -> Self {
+        ApplyError::Article(e)
+    }
+}
+
+impl From<sqlx::Error> for ApplyError {
+    fn from(e: sqlx::Error) -> Self {
+        ApplyError::Internal(e)
+    }
+}
+
+async fn apply(
+    db: &Db,
+    link_analyzer: &LinkAnalyzer,
+    events: &Events,
+    user: i32,
+    change: &PushChange,
+) -> Result<i32, ApplyError> {
+    let insert = ArticleInsert {
+        id: change.id,
+        title: &change.title,
+        content: change.content.clone(),
+        tags: change.tags.iter().map(|t| t.as_str()).collect(),
+        // keeps the stored override on update
+        language: None,
+        auto_tag: false,
+    };
+    let id = match change.id {
+        Some(id) => id,
+        None => return Ok(article::create(db, link_analyzer, events, user, insert).await?),
+    };
+    // changes of existing articles have to be based on the stored version,
+    // the save locks the article and checks its seq in the same transaction
+    let base_seq = change.base_seq.ok_or(ApplyError::Conflict)?;
+    let result = if change.deleted {
+        article::delete(db, events, user, id, Some(base_seq)).await
+    } else {
+        article::update(db, link_analyzer, events, user, insert, Some(base_seq)).await
+    };
+    match result {
+        Ok(()) => Ok(id),
+        // deleted since the client last synced
+        Err(ArticleError::Conflict | ArticleError::NotFound) => Err(ApplyError::Conflict),
+        Err(e) => Err(e.into()),
+    }
+}
+
+async fn get_seq(db: &Db, user: i32, id: i32) -> Result<Option<i64>, sqlx::Error> {
+    sqlx::query_scalar("SELECT seq FROM articles WHERE id = $1 AND user_id = $2")
+        .bind(id)
+        .bind(user)
+        .fetch_optional(db)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::links::test_analyzer;
+    use crate::db::{test_db, test_user};
+    use std::sync::Arc;
+
+    fn change(id: Option<i32>, base_seq: Option<i64>, title: &str) -> PushChange {
+        PushChange {
+            client_id: None,
+            id,
+            base_seq,
+            deleted: false,
+            title: title.to_owned(),
+            content: "content".to_owned(),
+            tags: vec![],
+        }
+    }
+
+    #[rocket::async_test]
+    async fn stale_pushes_conflict_and_rejected_ones_keep_the_base() {
+        let db = match test_db().await {
+            Some(db) => Arc::new(db),
+            None => return,
+        };
+        let user = test_user(&db).await;
+        let analyzer = test_analyzer(db.clone());
+        let events = Events::new(db.clone());
+        let push_one = |change: PushChange| {
+            let (db, analyzer, events) = (db.clone(), &analyzer, &events);
+            async move {
+                let mut results = push(&db, analyzer, events, user, vec![change]).await.unwrap();
+                results.remove(0)
+            }
+        };
+
+        let created = push_one(change(None, None, "Draft")).await;
+        let (id, base) = (created.id, created.seq.unwrap());
+
+        let rejected = push_one(change(id, Some(base), "")).await;
+        assert!(matches!(rejected.status, PushStatus::Rejected));
+        assert_eq!(rejected.seq, Some(base));
+
+        let applied = push_one(change(id, Some(base), "Edited")).await;
+        assert!(matches!(applied.status, PushStatus::Applied));
+        assert!(applied.seq.unwrap() > base);
+
+        let stale = push_one(change(id, Some(base), "Stale")).await;
+        assert!(matches!(stale.status, PushStatus::Conflict));
+        assert_eq!(stale.seq, applied.seq);
+        let article = article::get(&db, user, id.unwrap()).await.unwrap();
+        assert_eq!(article.title, "Edited");
+
+        let mut deleted = change(id, Some(base), "");
+        deleted.deleted = true;
+        assert!(matches!(push_one(deleted).await.status, PushStatus::Conflict));
+    }
+}
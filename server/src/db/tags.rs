@@ -1,17 +1,154 @@
+use super::article::{self, ArticleError, FieldError};
+use super::events::{EventKind, Events};
+use super::versions::Change;
 use super::Db;
-use rocket::serde::Serialize;
+use chrono::NaiveDateTime;
+use rocket::serde::{Deserialize, Serialize};
+use sqlx::{Executor, Postgres, Transaction};
+use std::collections::HashMap;
+use std::fmt::Write;
 
 // ========================== TYPES =======================
 
-#[derive(Debug, sqlx::FromRow, Serialize)]
+/// Separates namespaces in tag names, e.g. `lang/rust`
+pub const SEPARATOR: char = '/';
+const MAX_DESCRIPTION_LEN: usize = 500;
+const MAX_ICON_LEN: usize = 32;
+const MAX_SUGGESTIONS: u32 = 50;
+
+#[derive(Debug, Serialize)]
 pub struct Tag {
+    pub id: i32,
+    pub name: String,
+    /// Distinct articles with the tag or one of its descendants
+    pub num_articles: i64,
+    pub color: Option<String>,
+    pub description: Option<String>,
+    pub icon: Option<String>,
+    pub aliases: Vec<String>,
+    pub children: Vec<Tag>,
+}
+
+/// Alternative name resolving to the tag
+#[derive(Debug, sqlx::FromRow, Serialize)]
+pub struct TagAlias {
+    pub id: i32,
+    pub name: String,
+}
+
+#[derive(Debug, sqlx::FromRow, Serialize)]
+pub struct TagSuggestion {
+    pub id: i32,
+    pub name: String,
+    pub num_articles: i64,
+    pub last_used: Option<NaiveDateTime>,
+    /// Similar to the prefix, but not starting with it
+    pub fuzzy: bool,
+}
+
+#[derive(Debug, sqlx::FromRow, Serialize)]
+pub struct ScoredTag {
+    pub id: i32,
+    pub name: String,
+    /// Cosine similarity of the TF-IDF vectors, between 0 and 1
+    pub score: f64,
+}
+
+#[derive(Debug, sqlx::FromRow, Serialize)]
+pub struct GraphNode {
     pub id: i32,
     pub name: String,
     pub num_articles: i64,
 }
 
+/// Number of articles sharing both tags
+#[derive(Debug, sqlx::FromRow, Serialize)]
+pub struct GraphEdge {
+    pub source: i32,
+    pub target: i32,
+    pub weight: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TagGraph {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+impl TagGraph {
+    pub fn to_graphml(&self) -> String {
+        let mut out = String::from(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<graphml xmlns="http://graphml.graphdrawing.org/xmlns">
+  <key id="name" for="node" attr.name="name" attr.type="string"/>
+  <key id="num_articles" for="node" attr.name="num_articles" attr.type="long"/>
+  <key id="weight" for="edge" attr.name="weight" attr.type="long"/>
+  <graph id="tags" edgedefault="undirected">
+"#,
+        );
+        for node in &self.nodes {
+            write!(
+                out,
+                r#"    <node id="n{}">
+      <data key="name">{}</data>
+      <data key="num_articles">{}</data>
+    </node>
+"#,
+                node.id,
+                escape_xml(&node.name),
+                node.num_articles
+            )
+            .unwrap();
+        }
+        for edge in &self.edges {
+            writeln!(
+                out,
+                r#"    <edge source="n{}" target="n{}"><data key="weight">{}</data></edge>"#,
+                edge.source, edge.target, edge.weight
+            )
+            .unwrap();
+        }
+        out.push_str("  </graph>\n</graphml>\n");
+        out
+    }
+}
+
+/// Missing fields are kept, empty strings clear the metadata
+#[derive(Deserialize)]
+pub struct TagPatch<'a> {
+    pub name: Option<&'a str>,
+    pub color: Option<&'a str>,
+    pub description: Option<&'a str>,
+    pub icon: Option<&'a str>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct TagRow {
+    id: i32,
+    name: String,
+    num_articles: i64,
+    color: Option<String>,
+    description: Option<String>,
+    icon: Option<String>,
+    aliases: Vec<String>,
+}
+
+// ========================== ERRORS ======================
+
 #[derive(Debug, thiserror::Error, Serialize)]
 pub enum TagError {
+    #[error("Not found")]
+    NotFound,
+    #[error("Invalid fields")]
+    Invalid(Vec<FieldError>),
+    #[error("Tag already exists")]
+    Conflict,
+    #[error("Can't merge tag into itself")]
+    SameTag,
+    #[error("Unknown format")]
+    UnknownFormat,
+    #[error("Unknown tags")]
+    UnknownTags(Vec<String>),
     #[error("Internal")]
     Internal(
         #[from]
@@ -23,54 +160,922 @@ pub enum TagError {
 
 // ========================== FUNCTIONS ===================
 
+/// Returns the tag tree, siblings are ordered by number of articles
 pub async fn list(db: &Db, user: i32) -> Result<Vec<Tag>, TagError> {
-    Ok(sqlx::query_as(
-        "SELECT t.id, t.name, 
-        (SELECT COUNT(article_id) FROM article_tags at WHERE at.tag_id = t.id) as num_articles
+    let rows: Vec<TagRow> = sqlx::query_as(
+        "SELECT t.id, t.name, t.color, t.description, t.icon,
+        ARRAY(SELECT name FROM tag_aliases WHERE tag_id = t.id ORDER BY name) as aliases,
+        (SELECT COUNT(DISTINCT at.article_id) FROM article_tags at
+            JOIN tags c ON c.id = at.tag_id
+            WHERE c.user_id = t.user_id
+                AND (c.id = t.id OR starts_with(c.name, t.name || '/'))) as num_articles
         FROM tags t
         WHERE t.user_id = $1
-        ORDER BY num_articles DESC")
-        .bind(user)
-        .fetch_all(db)
-        .await?)
+        ORDER BY num_articles DESC, t.name",
+    )
+    .bind(user)
+    .fetch_all(db)
+    .await?;
+    let mut children: HashMap<Option<String>, Vec<TagRow>> = HashMap::new();
+    for row in rows {
+        children
+            .entry(parent_name(&row.name).map(str::to_owned))
+            .or_default()
+            .push(row);
+    }
+    Ok(build_tree(&mut children, None))
 }
 
-pub async fn update_article_tags(
+/// Tags starting with the prefix, filled up with similar tags.
+/// Ranked by number of articles and last use.
+pub async fn suggest(
+    db: &Db,
+    user: i32,
+    prefix: &str,
+    limit: u32,
+) -> Result<Vec<TagSuggestion>, TagError> {
+    let prefix = prefix.trim().to_lowercase();
+    let limit = limit.min(MAX_SUGGESTIONS);
+    let mut suggestions: Vec<TagSuggestion> = sqlx::query_as(
+        "
+        SELECT t.id, t.name, COUNT(at.article_id) as num_articles, MAX(a.updated_on) as last_used,
+            FALSE as fuzzy
+        FROM tags t
+            LEFT JOIN article_tags at ON at.tag_id = t.id
+            LEFT JOIN articles a ON a.id = at.article_id
+        WHERE t.user_id = $1 AND starts_with(t.name, $2)
+        GROUP BY t.id
+        ORDER BY num_articles DESC, last_used DESC NULLS LAST, t.name
+        LIMIT $3",
+    )
+    .bind(user)
+    .bind(&prefix)
+    .bind(limit)
+    .fetch_all(db)
+    .await?;
+    if prefix.is_empty() || suggestions.len() >= limit as usize {
+        return Ok(suggestions);
+    }
+    let found: Vec<i32> = suggestions.iter().map(|s| s.id).collect();
+    let similar: Vec<TagSuggestion> = sqlx::query_as(
+        "
+        SELECT t.id, t.name, COUNT(at.article_id) as num_articles, MAX(a.updated_on) as last_used,
+            TRUE as fuzzy
+        FROM tags t
+            LEFT JOIN article_tags at ON at.tag_id = t.id
+            LEFT JOIN articles a ON a.id = at.article_id
+        WHERE t.user_id = $1 AND t.name % $2 AND NOT t.id = ANY($4)
+        GROUP BY t.id
+        ORDER BY similarity(t.name, $2) DESC, num_articles DESC, last_used DESC NULLS LAST
+        LIMIT $3",
+    )
+    .bind(user)
+    .bind(&prefix)
+    .bind(limit - suggestions.len() as u32)
+    .bind(found)
+    .fetch_all(db)
+    .await?;
+    suggestions.extend(similar);
+    Ok(suggestions)
+}
+
+/// Co-occurrence graph of the tags. With a tag filter only the given
+/// tags, their edges and neighbours are included.
+/// Tags are given by name, alias or id.
+pub async fn graph(
     db: &Db,
     user: i32,
+    min_weight: i64,
+    tags: &[&str],
+) -> Result<TagGraph, TagError> {
+    let tags = article::resolve_tags(db, user, tags)
+        .await
+        .map_err(|e| match e {
+            ArticleError::UnknownTags(names) => TagError::UnknownTags(names),
+            ArticleError::Internal(e) => TagError::Internal(e),
+            _ => TagError::NotFound,
+        })?;
+    let edges: Vec<GraphEdge> = sqlx::query_as(
+        "
+        SELECT a1.tag_id as source, a2.tag_id as target, COUNT(*) as weight
+        FROM article_tags a1
+            JOIN article_tags a2 ON a2.article_id = a1.article_id AND a1.tag_id < a2.tag_id
+            JOIN tags t ON t.id = a1.tag_id
+        WHERE t.user_id = $1
+            AND (CARDINALITY($3::int[]) = 0 OR a1.tag_id = ANY($3) OR a2.tag_id = ANY($3))
+        GROUP BY a1.tag_id, a2.tag_id
+        HAVING COUNT(*) >= $2
+        ORDER BY weight DESC, source, target",
+    )
+    .bind(user)
+    .bind(min_weight)
+    .bind(&tags)
+    .fetch_all(db)
+    .await?;
+    let node_ids = if tags.is_empty() {
+        None
+    } else {
+        let mut ids = tags;
+        ids.extend(edges.iter().flat_map(|e| [e.source, e.target]));
+        Some(ids)
+    };
+    let nodes = sqlx::query_as(
+        "
+        SELECT t.id, t.name, COUNT(at.article_id) as num_articles
+        FROM tags t
+            JOIN article_tags at ON at.tag_id = t.id
+        WHERE t.user_id = $1 AND ($2::int[] IS NULL OR t.id = ANY($2))
+        GROUP BY t.id
+        ORDER BY t.id",
+    )
+    .bind(user)
+    .bind(node_ids)
+    .fetch_all(db)
+    .await?;
+    Ok(TagGraph { nodes, edges })
+}
+
+/// Ranks existing tags by similarity of the article lexemes
+/// to the lexemes of all other articles with the tag
+pub async fn score_for_article<'e, E>(
+    executor: E,
+    user: i32,
+    article: i32,
+    limit: u32,
+) -> Result<Vec<ScoredTag>, sqlx::Error>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    sqlx::query_as(
+        "
+        WITH docs AS (
+            SELECT a.id, l.lexeme, COALESCE(ARRAY_LENGTH(l.positions, 1), 1) as tf
+            FROM articles a, UNNEST(a.search_vector) l
+            WHERE a.user_id = $1
+        ),
+        idf AS (
+            SELECT lexeme,
+                LN((SELECT COUNT(*) FROM articles WHERE user_id = $1)::float8 / COUNT(*)) as idf
+            FROM docs
+            GROUP BY lexeme
+        ),
+        target AS (
+            SELECT lexeme, tf * idf as w FROM docs JOIN idf USING (lexeme) WHERE id = $2
+        ),
+        profiles AS (
+            SELECT at.tag_id, lexeme, SUM(tf) * idf as w
+            FROM article_tags at
+                JOIN docs ON docs.id = at.article_id
+                JOIN idf USING (lexeme)
+            WHERE at.article_id <> $2
+            GROUP BY at.tag_id, lexeme, idf
+        ),
+        norms AS (
+            SELECT tag_id, SQRT(SUM(w * w)) as norm FROM profiles GROUP BY tag_id
+        )
+        SELECT t.id, t.name,
+            SUM(p.w * target.w)
+                / NULLIF(n.norm * (SELECT SQRT(SUM(w * w)) FROM target), 0) as score
+        FROM profiles p
+            JOIN target USING (lexeme)
+            JOIN norms n USING (tag_id)
+            JOIN tags t ON t.id = p.tag_id
+        GROUP BY t.id, n.norm
+        HAVING SUM(p.w * target.w) / NULLIF(n.norm * (SELECT SQRT(SUM(w * w)) FROM target), 0) > 0
+        ORDER BY score DESC
+        LIMIT $3",
+    )
+    .bind(user)
+    .bind(article)
+    .bind(limit)
+    .fetch_all(executor)
+    .await
+}
+
+/// Applies the name and metadata changes in one transaction
+pub async fn update(
+    db: &Db,
+    events: &Events,
+    user: i32,
+    id: i32,
+    patch: TagPatch<'_>,
+) -> Result<(), TagError> {
+    validate_metadata(&patch)?;
+    let mut tx = db.begin().await?;
+    let name = get_name(&mut tx, user, id).await?;
+    let (articles, affected) = match patch.name {
+        Some(new_name) => {
+            let (new_name, articles) = rename(&mut tx, user, &name, new_name).await?;
+            (articles, vec![name, new_name])
+        }
+        None => (vec![], vec![name]),
+    };
+    sqlx::query(
+        "
+        UPDATE tags SET
+        color = CASE WHEN $2::text IS NULL THEN color ELSE NULLIF($2, '') END,
+        description = CASE WHEN $3::text IS NULL THEN description ELSE NULLIF($3, '') END,
+        icon = CASE WHEN $4::text IS NULL THEN icon ELSE NULLIF($4, '') END
+        WHERE id = $1",
+    )
+    .bind(id)
+    .bind(patch.color)
+    .bind(patch.description)
+    .bind(patch.icon)
+    .execute(&mut tx)
+    .await?;
+    // cleared metadata or a moved subtree can leave orphans
+    remove_orphans(&mut tx, user, &affected).await?;
+    tx.commit().await?;
+    notify_updated(db, events, user, &articles).await;
+    Ok(())
+}
+
+/// Deletes the tag and its descendants from all articles
+pub async fn delete(db: &Db, events: &Events, user: i32, id: i32) -> Result<(), TagError> {
+    let mut tx = db.begin().await?;
+    let name = get_name(&mut tx, user, id).await?;
+    let subtree = subtree_ids(&mut tx, user, &name).await?;
+    let articles = touch_articles(&mut tx, user, &subtree).await?;
+    sqlx::query("DELETE FROM article_tags WHERE tag_id = ANY($1)")
+        .bind(&subtree)
+        .execute(&mut tx)
+        .await?;
+    sqlx::query("DELETE FROM tags WHERE id = ANY($1)")
+        .bind(&subtree)
+        .execute(&mut tx)
+        .await?;
+    // the parents might have been kept only for the subtree
+    remove_orphans(&mut tx, user, &[name]).await?;
+    tx.commit().await?;
+    notify_updated(db, events, user, &articles).await;
+    Ok(())
+}
+
+/// Moves all articles of the tag to `into` and deletes the tag.
+/// A tag with descendants is kept as their namespace.
+pub async fn merge(
+    db: &Db,
+    events: &Events,
+    user: i32,
+    id: i32,
+    into: i32,
+) -> Result<(), TagError> {
+    if id == into {
+        return Err(TagError::SameTag);
+    }
+    let mut tx = db.begin().await?;
+    let name = get_name(&mut tx, user, id).await?;
+    get_name(&mut tx, user, into).await?;
+    let articles = touch_articles(&mut tx, user, &[id]).await?;
+    sqlx::query(
+        "INSERT INTO article_tags (article_id, tag_id)
+        SELECT article_id, $2 FROM article_tags WHERE tag_id = $1
+        ON CONFLICT DO NOTHING",
+    )
+    .bind(id)
+    .bind(into)
+    .execute(&mut tx)
+    .await?;
+    sqlx::query("DELETE FROM article_tags WHERE tag_id = $1")
+        .bind(id)
+        .execute(&mut tx)
+        .await?;
+    sqlx::query("UPDATE tag_aliases SET tag_id = $2 WHERE tag_id = $1")
+        .bind(id)
+        .bind(into)
+        .execute(&mut tx)
+        .await?;
+    // keep saved searches pointing to the merged tag
+    sqlx::query(
+        "UPDATE saved_searches
+        SET tags = ARRAY_REPLACE(tags, $2, $3), exclude_tags = ARRAY_REPLACE(exclude_tags, $2, $3)
+        WHERE user_id = $1",
+    )
+    .bind(user)
+    .bind(id)
+    .bind(into)
+    .execute(&mut tx)
+    .await?;
+    sqlx::query(
+        "DELETE FROM tags t WHERE id = $1
+        AND NOT EXISTS(
+            SELECT 1 FROM tags c WHERE c.user_id = t.user_id AND starts_with(c.name, t.name || '/')
+        )",
+    )
+    .bind(id)
+    .execute(&mut tx)
+    .await?;
+    remove_orphans(&mut tx, user, &[name]).await?;
+    tx.commit().await?;
+    notify_updated(db, events, user, &articles).await;
+    Ok(())
+}
+
+pub async fn aliases(db: &Db, user: i32, id: i32) -> Result<Vec<TagAlias>, TagError> {
+    get_name(db, user, id).await?;
+    Ok(
+        sqlx::query_as("SELECT id, name FROM tag_aliases WHERE tag_id = $1 ORDER BY name")
+            .bind(id)
+            .fetch_all(db)
+            .await?,
+    )
+}
+
+/// Adds an alias, it can't be the name of a tag or another alias
+pub async fn create_alias(
+    db: &Db,
+    events: &Events,
+    user: i32,
+    id: i32,
+    name: &str,
+) -> Result<i32, TagError> {
+    let name = name.trim().to_lowercase();
+    if let Some(e) = article::validate_tag("name".to_owned(), &name) {
+        return Err(TagError::Invalid(vec![e]));
+    }
+    let mut tx = db.begin().await?;
+    get_name(&mut tx, user, id).await?;
+    let taken: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM tags WHERE user_id = $1 AND name = $2)
+        OR EXISTS(SELECT 1 FROM tag_aliases WHERE user_id = $1 AND name = $2)",
+    )
+    .bind(user)
+    .bind(&name)
+    .fetch_one(&mut tx)
+    .await?;
+    if taken {
+        return Err(TagError::Conflict);
+    }
+    let alias = sqlx::query_scalar(
+        "INSERT INTO tag_aliases (user_id, tag_id, name) VALUES ($1, $2, $3)
+        RETURNING id",
+    )
+    .bind(user)
+    .bind(id)
+    .bind(&name)
+    .fetch_one(&mut tx)
+    .await?;
+    let articles = touch_articles(&mut tx, user, &[id]).await?;
+    tx.commit().await?;
+    notify_updated(db, events, user, &articles).await;
+    Ok(alias)
+}
+
+pub async fn delete_alias(
+    db: &Db,
+    events: &Events,
+    user: i32,
+    id: i32,
+    alias: i32,
+) -> Result<(), TagError> {
+    let mut tx = db.begin().await?;
+    let name = get_name(&mut tx, user, id).await?;
+    let result = sqlx::query("DELETE FROM tag_aliases WHERE id = $1 AND tag_id = $2")
+        .bind(alias)
+        .bind(id)
+        .execute(&mut tx)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(TagError::NotFound);
+    }
+    let articles = touch_articles(&mut tx, user, &[id]).await?;
+    // the tag might have been kept only for the alias
+    remove_orphans(&mut tx, user, &[name]).await?;
+    tx.commit().await?;
+    notify_updated(db, events, user, &articles).await;
+    Ok(())
+}
+
+/// Replaces the tags of the article, part of the article write
+pub async fn update_article_tags(
+    tx: &mut Transaction<'_, Postgres>,
+    user: i32,
     article: i32,
     tags: &[&str],
 ) -> Result<(), sqlx::Error> {
-    // delete all tag mappings
-    sqlx::query("DELETE FROM article_tags WHERE article_id = $1")
-        .bind(article)
-        .execute(db)
-        .await?;
-    // insert all tags TODO: Improve!
-    for tag in tags {
-        sqlx::query("INSERT INTO tags (user_id, name) VALUES ($1, LOWER($2))
-            ON CONFLICT DO NOTHING")
-            .bind(user)
-            .bind(tag)
-            .execute(db)
-            .await?;
-        let id: i32 = sqlx::query_scalar("SELECT id FROM tags WHERE user_id = $1 AND name = LOWER($2)")
+    let names = canonical_names(&mut *tx, user, tags).await?;
+    let canonical: Vec<&str> = names.iter().map(String::as_str).collect();
+    // namespaces are tags too
+    upsert_tags(tx, user, &canonical).await?;
+    // replace the mappings that changed
+    let detached: Vec<String> = sqlx::query_scalar(
+        "
+        WITH wanted AS (
+            SELECT id FROM tags WHERE user_id = $1 AND name = ANY($3)
+        ),
+        added AS (
+            INSERT INTO article_tags (article_id, tag_id)
+            SELECT $2, id FROM wanted
+            ON CONFLICT DO NOTHING
+        ),
+        removed AS (
+            DELETE FROM article_tags
+            WHERE article_id = $2 AND tag_id NOT IN (SELECT id FROM wanted)
+            RETURNING tag_id
+        )
+        SELECT t.name FROM removed JOIN tags t ON t.id = removed.tag_id",
+    )
+    .bind(user)
+    .bind(article)
+    .bind(names)
+    .fetch_all(&mut *tx)
+    .await?;
+    if !detached.is_empty() {
+        remove_orphans(tx, user, &detached).await?;
+    }
+    Ok(())
+}
+
+// ========================== HELPERS =====================
+
+async fn get_name<'e, E>(executor: E, user: i32, id: i32) -> Result<String, TagError>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    sqlx::query_scalar("SELECT name FROM tags WHERE id = $1 AND user_id = $2")
+        .bind(id)
+        .bind(user)
+        .fetch_optional(executor)
+        .await?
+        .ok_or(TagError::NotFound)
+}
+
+/// Renames the tag and its descendants, names are compared case-insensitively.
+/// Returns the new name and the articles of the subtree.
+async fn rename(
+    tx: &mut Transaction<'_, Postgres>,
+    user: i32,
+    old_name: &str,
+    name: &str,
+) -> Result<(String, Vec<i32>), TagError> {
+    let name = name.trim().to_lowercase();
+    if let Some(e) = article::validate_tag("name".to_owned(), &name) {
+        return Err(TagError::Invalid(vec![e]));
+    }
+    if name.starts_with(&format!("{}{}", old_name, SEPARATOR)) {
+        return Err(TagError::Conflict);
+    }
+    // any renamed name taken by an alias or a tag outside of the subtree
+    let taken: bool = sqlx::query_scalar(
+        "
+        SELECT EXISTS(
+            SELECT 1 FROM tag_aliases
+            WHERE user_id = $1 AND (name = $2 OR starts_with($2, name || '/'))
+        ) OR EXISTS(
+            SELECT 1 FROM tags s
+                JOIN tags o ON o.user_id = s.user_id
+                    AND o.name = $2 || SUBSTR(s.name, LENGTH($3) + 1)
+            WHERE s.user_id = $1
+                AND (s.name = $3 OR starts_with(s.name, $3 || '/'))
+                AND NOT (o.name = $3 OR starts_with(o.name, $3 || '/'))
+        )",
+    )
+    .bind(user)
+    .bind(&name)
+    .bind(old_name)
+    .fetch_one(&mut *tx)
+    .await?;
+    if taken {
+        return Err(TagError::Conflict);
+    }
+    let subtree = subtree_ids(tx, user, old_name).await?;
+    let articles = touch_articles(tx, user, &subtree).await?;
+    sqlx::query(
+        "UPDATE tags SET name = $2 || SUBSTR(name, LENGTH($3) + 1) WHERE id = ANY($1)",
+    )
+    .bind(&subtree)
+    .bind(&name)
+    .bind(old_name)
+    .execute(&mut *tx)
+    .await?;
+    upsert_tags(tx, user, &[name.as_str()]).await?;
+    Ok((name, articles))
+}
+
+/// Ids of the tag and its descendants
+async fn subtree_ids(
+    tx: &mut Transaction<'_, Postgres>,
+    user: i32,
+    name: &str,
+) -> Result<Vec<i32>, sqlx::Error> {
+    sqlx::query_scalar(
+        "SELECT id FROM tags WHERE user_id = $1 AND (name = $2 OR starts_with(name, $2 || '/'))",
+    )
+    .bind(user)
+    .bind(name)
+    .fetch_all(&mut *tx)
+    .await
+}
+
+/// Bumps the sync sequence of the articles with any of the tags,
+/// so they are sent again with the changed tags
+async fn touch_articles(
+    tx: &mut Transaction<'_, Postgres>,
+    user: i32,
+    tags: &[i32],
+) -> Result<Vec<i32>, sqlx::Error> {
+    sqlx::query_scalar(
+        "
+        UPDATE articles a SET seq = nextval('change_seq')
+        WHERE a.user_id = $1 AND EXISTS(
+            SELECT 1 FROM article_tags at WHERE at.article_id = a.id AND at.tag_id = ANY($2)
+        )
+        RETURNING a.id",
+    )
+    .bind(user)
+    .bind(tags)
+    .fetch_all(&mut *tx)
+    .await
+}
+
+/// Commits versions and notifies subscribers of articles whose tags changed
+async fn notify_updated(
+    db: &Db,
+    events: &Events,
+    user: i32,
+    articles: &[i32],
+) {
+    for &id in articles {
+        super::versions::commit(db, user, id, Change::Updated).await;
+        events.emit(user, EventKind::ArticleUpdated { id }).await;
+    }
+}
+
+/// Lowercases the names and replaces aliases with their tag names,
+/// an alias also stands for its tag as a namespace, e.g. `k8s/helm`
+pub async fn canonical_names<'e, E>(
+    executor: E,
+    user: i32,
+    tags: &[&str],
+) -> Result<Vec<String>, sqlx::Error>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    let names: Vec<String> = tags.iter().map(|t| t.to_lowercase()).collect();
+    if names.is_empty() {
+        return Ok(names);
+    }
+    sqlx::query_scalar(
+        "
+        SELECT COALESCE((
+            SELECT t.name || SUBSTR(f.name, LENGTH(al.name) + 1)
+            FROM tag_aliases al JOIN tags t ON t.id = al.tag_id
+            WHERE al.user_id = $1 AND (al.name = f.name OR starts_with(f.name, al.name || '/'))
+            ORDER BY LENGTH(al.name) DESC
+            LIMIT 1
+        ), f.name)
+        FROM UNNEST($2::text[]) WITH ORDINALITY f (name, i)
+        ORDER BY f.i",
+    )
+    .bind(user)
+    .bind(names)
+    .fetch_all(executor)
+    .await
+}
+
+/// Creates the missing tags and their parents, names must be canonical
+async fn upsert_tags(
+    tx: &mut Transaction<'_, Postgres>,
+    user: i32,
+    tags: &[&str],
+) -> Result<(), sqlx::Error> {
+    let mut names: Vec<String> = tags
+        .iter()
+        .flat_map(|tag| {
+            tag.match_indices(SEPARATOR)
+                .map(|(i, _)| i)
+                .chain(std::iter::once(tag.len()))
+                .map(move |i| tag[..i].to_lowercase())
+        })
+        .collect();
+    names.sort();
+    names.dedup();
+    if names.is_empty() {
+        return Ok(());
+    }
+    sqlx::query(
+        "INSERT INTO tags (user_id, name) SELECT $1, * FROM UNNEST($2::text[])
+        ON CONFLICT DO NOTHING",
+    )
+    .bind(user)
+    .bind(names)
+    .execute(&mut *tx)
+    .await?;
+    Ok(())
+}
+
+/// Deletes tags without metadata and without articles in their subtree.
+/// Only the tags with the affected names and their parents are checked.
+async fn remove_orphans(
+    tx: &mut Transaction<'_, Postgres>,
+    user: i32,
+    affected: &[String],
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "DELETE FROM tags t WHERE
+        user_id = $1 AND
+        color IS NULL AND description IS NULL AND icon IS NULL AND
+        NOT EXISTS(SELECT 1 FROM tag_aliases WHERE tag_id = t.id) AND
+        EXISTS(
+            SELECT 1 FROM UNNEST($2::text[]) d (name)
+            WHERE d.name = t.name OR starts_with(d.name, t.name || '/')
+        ) AND
+        NOT EXISTS(
+            SELECT 1 FROM article_tags at JOIN tags c ON c.id = at.tag_id
+            WHERE c.user_id = t.user_id
+                AND (c.id = t.id OR starts_with(c.name, t.name || '/'))
+        )",
+    )
+    .bind(user)
+    .bind(affected)
+    .execute(&mut *tx)
+    .await?;
+    Ok(())
+}
+
+fn validate_metadata(patch: &TagPatch<'_>) -> Result<(), TagError> {
+    let mut errors = Vec::new();
+    match patch.color {
+        Some(color) if !color.is_empty() && !is_hex_color(color) => {
+            errors.push(article::field_error(
+                "color",
+                "bad_color",
+                "Color must be in #rrggbb format".to_owned(),
+            ));
+        }
+        _ => (),
+    }
+    if patch.description.map(|d| d.chars().count()).unwrap_or(0) > MAX_DESCRIPTION_LEN {
+        errors.push(article::field_error(
+            "description",
+            "too_long",
+            format!("Description is longer than {} characters", MAX_DESCRIPTION_LEN),
+        ));
+    }
+    if patch.icon.map(|i| i.chars().count()).unwrap_or(0) > MAX_ICON_LEN {
+        errors.push(article::field_error(
+            "icon",
+            "too_long",
+            format!("Icon is longer than {} characters", MAX_ICON_LEN),
+        ));
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(TagError::Invalid(errors))
+    }
+}
+
+fn is_hex_color(color: &str) -> bool {
+    color.len() == 7
+        && color.starts_with('#')
+        && color[1..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn parent_name(name: &str) -> Option<&str> {
+    name.rfind(SEPARATOR).map(|i| &name[..i])
+}
+
+fn build_tree(
+    children: &mut HashMap<Option<String>, Vec<TagRow>>,
+    parent: Option<String>,
+) -> Vec<Tag> {
+    let rows = children.remove(&parent).unwrap_or_default();
+    rows.into_iter()
+        .map(|row| Tag {
+            children: build_tree(children, Some(row.name.clone())),
+            id: row.id,
+            name: row.name,
+            num_articles: row.num_articles,
+            color: row.color,
+            description: row.description,
+            icon: row.icon,
+            aliases: row.aliases,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{test_article, test_db, test_user};
+    use std::sync::Arc;
+
+    async fn tag_id(db: &Db, user: i32, name: &str) -> Option<i32> {
+        sqlx::query_scalar("SELECT id FROM tags WHERE user_id = $1 AND name = $2")
             .bind(user)
-            .bind(tag)
-            .fetch_one(db)
-            .await?;
-        sqlx::query("INSERT INTO article_tags (article_id, tag_id) VALUES ($1, $2)")
+            .bind(name)
+            .fetch_optional(db)
+            .await
+            .unwrap()
+    }
+
+    #[rocket::async_test]
+    async fn suggest_ranks_prefix_matches_before_similar_tags() {
+        let db = match test_db().await {
+            Some(db) => db,
+            None => return,
+        };
+        let user = test_user(&db).await;
+        test_article(&db, user, &["web_dev"]).await;
+        for _ in 0..2 {
+            test_article(&db, user, &["web_api"]).await;
+        }
+        for _ in 0..3 {
+            test_article(&db, user, &["webdev"]).await;
+        }
+        test_article(&db, user, &["rust"]).await;
+
+        let suggestions = suggest(&db, user, " Web_", 10).await.unwrap();
+        let ranked: Vec<(&str, bool)> = suggestions
+            .iter()
+            .map(|s| (s.name.as_str(), s.fuzzy))
+            .collect();
+        assert_eq!(
+            ranked,
+            vec![("web_api", false), ("web_dev", false), ("webdev", true)]
+        );
+
+        let suggestions = suggest(&db, user, "web_", 1).await.unwrap();
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].name, "web_api");
+    }
+
+    async fn set_text(db: &Db, article: i32, text: &str) {
+        sqlx::query("UPDATE articles SET search_vector = to_tsvector('english', $2) WHERE id = $1")
             .bind(article)
-            .bind(id)
+            .bind(text)
             .execute(db)
-            .await?;
+            .await
+            .unwrap();
+    }
+
+    #[rocket::async_test]
+    async fn tags_of_similar_articles_are_scored() {
+        let db = match test_db().await {
+            Some(db) => db,
+            None => return,
+        };
+        let user = test_user(&db).await;
+        let rust = test_article(&db, user, &["rust"]).await;
+        set_text(&db, rust, "rust borrow checker lifetimes").await;
+        let cooking = test_article(&db, user, &["cooking"]).await;
+        set_text(&db, cooking, "pasta with tomato and basil").await;
+        let target = test_article(&db, user, &[]).await;
+        set_text(&db, target, "lifetimes and the borrow checker").await;
+
+        let scored = score_for_article(&db, user, target, 10).await.unwrap();
+        let names: Vec<&str> = scored.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["rust"]);
+        assert!(scored[0].score > 0.0 && scored[0].score <= 1.0);
+    }
+
+    async fn article_tags(db: &Db, id: i32) -> Vec<String> {
+        sqlx::query_scalar(
+            "SELECT t.name FROM article_tags at JOIN tags t ON t.id = at.tag_id
+            WHERE at.article_id = $1
+            ORDER BY t.name",
+        )
+        .bind(id)
+        .fetch_all(db)
+        .await
+        .unwrap()
+    }
+
+    async fn article_seq(db: &Db, id: i32) -> i64 {
+        sqlx::query_scalar("SELECT seq FROM articles WHERE id = $1")
+            .bind(id)
+            .fetch_one(db)
+            .await
+            .unwrap()
+    }
+
+    #[rocket::async_test]
+    async fn merge_moves_articles_aliases_and_searches() {
+        let db = match test_db().await {
+            Some(db) => db,
+            None => return,
+        };
+        let events = Events::new(Arc::new(db.clone()));
+        let user = test_user(&db).await;
+        let first = test_article(&db, user, &["rust"]).await;
+        let second = test_article(&db, user, &["rust", "lang"]).await;
+        let rust = tag_id(&db, user, "rust").await.unwrap();
+        let lang = tag_id(&db, user, "lang").await.unwrap();
+        create_alias(&db, &events, user, rust, "rs").await.unwrap();
+        sqlx::query("INSERT INTO saved_searches (user_id, name, tags) VALUES ($1, 'Rust', $2)")
+            .bind(user)
+            .bind(vec![rust])
+            .execute(&db)
+            .await
+            .unwrap();
+        let seq = article_seq(&db, first).await;
+
+        merge(&db, &events, user, rust, lang).await.unwrap();
+        assert_eq!(article_tags(&db, first).await, vec!["lang"]);
+        assert_eq!(article_tags(&db, second).await, vec!["lang"]);
+        assert!(article_seq(&db, first).await > seq);
+        assert_eq!(tag_id(&db, user, "rust").await, None);
+        assert_eq!(canonical_names(&db, user, &["rs"]).await.unwrap(), vec!["lang"]);
+        let searched: Vec<i32> =
+            sqlx::query_scalar("SELECT tags FROM saved_searches WHERE user_id = $1")
+                .bind(user)
+                .fetch_one(&db)
+                .await
+                .unwrap();
+        assert_eq!(searched, vec![lang]);
+    }
+
+    #[rocket::async_test]
+    async fn failed_merge_changes_nothing() {
+        let db = match test_db().await {
+            Some(db) => db,
+            None => return,
+        };
+        let events = Events::new(Arc::new(db.clone()));
+        let user = test_user(&db).await;
+        let other = test_user(&db).await;
+        let article = test_article(&db, user, &["rust"]).await;
+        test_article(&db, other, &["lang"]).await;
+        let rust = tag_id(&db, user, "rust").await.unwrap();
+        let foreign = tag_id(&db, other, "lang").await.unwrap();
+        let seq = article_seq(&db, article).await;
+
+        assert!(matches!(
+            merge(&db, &events, user, rust, foreign).await,
+            Err(TagError::NotFound)
+        ));
+        assert_eq!(article_tags(&db, article).await, vec!["rust"]);
+        assert_eq!(article_seq(&db, article).await, seq);
+    }
+
+    #[rocket::async_test]
+    async fn rename_moves_subtree_and_removes_old_parents() {
+        let db = match test_db().await {
+            Some(db) => db,
+            None => return,
+        };
+        let events = Events::new(Arc::new(db.clone()));
+        let user = test_user(&db).await;
+        let article = test_article(&db, user, &["a/b/c"]).await;
+        let b = tag_id(&db, user, "a/b").await.unwrap();
+        let seq = article_seq(&db, article).await;
+        let patch = TagPatch {
+            name: Some("X/b"),
+            color: Some("#ff0000"),
+            description: None,
+            icon: None,
+        };
+
+        update(&db, &events, user, b, patch).await.unwrap();
+        assert_eq!(article_tags(&db, article).await, vec!["x/b/c"]);
+        assert!(article_seq(&db, article).await > seq);
+        assert_eq!(tag_id(&db, user, "a").await, None);
+        assert!(tag_id(&db, user, "x").await.is_some());
+        let color: Option<String> = sqlx::query_scalar("SELECT color FROM tags WHERE id = $1")
+            .bind(b)
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        assert_eq!(color.as_deref(), Some("#ff0000"));
+    }
+
+    #[rocket::async_test]
+    async fn conflicting_rename_keeps_metadata() {
+        let db = match test_db().await {
+            Some(db) => db,
+            None => return,
+        };
+        let events = Events::new(Arc::new(db.clone()));
+        let user = test_user(&db).await;
+        test_article(&db, user, &["rust", "lang"]).await;
+        let rust = tag_id(&db, user, "rust").await.unwrap();
+        let patch = TagPatch {
+            name: Some("lang"),
+            color: Some("#ff0000"),
+            description: None,
+            icon: None,
+        };
+
+        assert!(matches!(
+            update(&db, &events, user, rust, patch).await,
+            Err(TagError::Conflict)
+        ));
+        let color: Option<String> = sqlx::query_scalar("SELECT color FROM tags WHERE id = $1")
+            .bind(rust)
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        assert_eq!(color, None);
     }
-    // remove orphans
-    sqlx::query("DELETE FROM tags t WHERE 
-        user_id = $1 AND
-        (SELECT COUNT(article_id) FROM article_tags WHERE tag_id = t.id) = 0")
-        .bind(user)
-        .execute(db)
-        .await?;
-    Ok(())
 }
@@ -0,0 +1,217 @@
+use super::article::{self, ArticleError, ArticleInsert};
+use super::crypto;
+use super::events::Events;
+use super::links::Analyzer as LinkAnalyzer;
+use super::Db;
+use crate::utils::extractor;
+use rocket::serde::Serialize;
+use sqlx::{Postgres, Transaction};
+
+// ========================== TYPES =======================
+
+#[derive(Debug, sqlx::FromRow, Serialize)]
+pub struct Task {
+    pub id: i32,
+    pub article_id: i32,
+    pub article_title: String,
+    pub line: i32,
+    pub text: String,
+    pub checked: bool,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct TaskPosition {
+    article_id: i32,
+    line: i32,
+    checked: bool,
+}
+
+#[derive(Debug)]
+pub struct ListOptions {
+    pub tags: Vec<i32>,
+    pub all_tags: bool,
+}
+
+// ========================== ERRORS ======================
+
+#[derive(Debug, thiserror::Error, Serialize)]
+pub enum TaskError {
+    #[error("Not found")]
+    NotFound,
+    #[error(transparent)]
+    Article(#[from] ArticleError),
+    #[error("Internal")]
+    Internal(
+        #[from]
+        #[source]
+        #[serde(skip)]
+        sqlx::Error,
+    ),
+}
+
+// ========================== FUNCTIONS ===================
+
+/// Open tasks, filter tags match themselves and their descendants
+pub async fn list(db: &Db, user: i32, opt: ListOptions) -> Result<Vec<Task>, TaskError> {
+    let (or_tags, all_tags) = if opt.all_tags {
+        (vec![], opt.tags)
+    } else {
+        (opt.tags, vec![])
+    };
+    let mut tasks: Vec<Task> = sqlx::query_as(
+        "
+        SELECT t.id, t.article_id, t.line, t.text, t.checked, a.title as article_title
+        FROM tasks t
+            JOIN articles a ON a.id = t.article_id
+        WHERE t.user_id = $1 AND NOT t.checked
+            AND (CARDINALITY($2::int[]) = 0 OR EXISTS(
+                SELECT 1 FROM article_tags fat
+                    JOIN tags c ON c.id = fat.tag_id
+                    JOIN tags p ON p.id = ANY($2) AND p.user_id = t.user_id
+                WHERE fat.article_id = t.article_id
+                    AND (c.id = p.id OR starts_with(c.name, p.name || '/'))
+            ))
+            AND NOT EXISTS(
+                SELECT 1 FROM UNNEST($3::int[]) ft (id)
+                WHERE NOT EXISTS(
+                    SELECT 1 FROM article_tags fat
+                        JOIN tags c ON c.id = fat.tag_id
+                        JOIN tags p ON p.id = ft.id AND p.user_id = t.user_id
+                    WHERE fat.article_id = t.article_id
+                        AND (c.id = p.id OR starts_with(c.name, p.name || '/'))
+                )
+            )
+        ORDER BY a.updated_on DESC, t.line",
+    )
+    .bind(user)
+    .bind(or_tags)
+    .bind(all_tags)
+    .fetch_all(db)
+    .await?;
+    for task in &mut tasks {
+        task.text = crypto::decrypt(db, user, std::mem::take(&mut task.text)).await?;
+    }
+    Ok(tasks)
+}
+
+/// Flips the checkbox of the task in the article content.
+/// Returns the new checked state.
+pub async fn toggle(
+    db: &Db,
+    link_analyzer: &LinkAnalyzer,
+    events: &Events,
+    user: i32,
+    id: i32,
+) -> Result<bool, TaskError> {
+    let task = sqlx::query_as::<_, TaskPosition>(
+        "SELECT article_id, line, checked FROM tasks WHERE id = $1 AND user_id = $2",
+    )
+    .bind(id)
+    .bind(user)
+    .fetch_optional(db)
+    .await?
+    .ok_or(TaskError::NotFound)?;
+    let stored = article::get(db, user, task.article_id).await?;
+    let content = extractor::set_task_checked(&stored.content, task.line, !task.checked)
+        .ok_or(TaskError::NotFound)?;
+    let insert = ArticleInsert {
+        id: Some(stored.id),
+        title: &stored.title,
+        content,
+        tags: stored.tags.iter().map(|t| t.as_str()).collect(),
+        language: stored.language.as_deref(),
+        auto_tag: false,
+    };
+    article::update(db, link_analyzer, events, user, insert, None).await?;
+    Ok(!task.checked)
+}
+
+/// Replaces the tasks of the article, the pool only provides the encryption key
+pub async fn update_article_tasks(
+    db: &Db,
+    tx: &mut Transaction<'_, Postgres>,
+    user: i32,
+    article: i32,
+    tasks: &[extractor::Task],
+) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM tasks WHERE article_id = $1")
+        .bind(article)
+        .execute(&mut *tx)
+        .await?;
+    if tasks.is_empty() {
+        return Ok(());
+    }
+    let lines: Vec<i32> = tasks.iter().map(|t| t.line).collect();
+    let mut texts = Vec::with_capacity(tasks.len());
+    for task in tasks {
+        texts.push(crypto::encrypt(db, user, &task.text).await?);
+    }
+    let checked: Vec<bool> = tasks.iter().map(|t| t.checked).collect();
+    sqlx::query(
+        "
+        INSERT INTO tasks (user_id, article_id, line, text, checked)
+        SELECT $1, $2, * FROM UNNEST($3::int[], $4::text[], $5::bool[])",
+    )
+    .bind(user)
+    .bind(article)
+    .bind(lines)
+    .bind(texts)
+    .bind(checked)
+    .execute(&mut *tx)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{test_article, test_db, test_user};
+
+    #[rocket::async_test]
+    async fn tag_filters_include_descendants() {
+        let db = match test_db().await {
+            Some(db) => db,
+            None => return,
+        };
+        let user = test_user(&db).await;
+        let helm = test_article(&db, user, &["kubernetes/helm"]).await;
+        let other = test_article(&db, user, &["rust"]).await;
+        for article in [helm, other] {
+            let task = extractor::Task {
+                text: "todo".to_owned(),
+                checked: false,
+                line: 1,
+            };
+            let mut tx = db.begin().await.unwrap();
+            update_article_tasks(&db, &mut tx, user, article, &[task]).await.unwrap();
+            tx.commit().await.unwrap();
+        }
+        let tags: Vec<i32> = sqlx::query_scalar(
+            "SELECT id FROM tags WHERE user_id = $1 AND name IN ('kubernetes', 'rust')
+            ORDER BY name",
+        )
+        .bind(user)
+        .fetch_all(&db)
+        .await
+        .unwrap();
+        let articles = |tasks: Vec<Task>| -> Vec<i32> {
+            tasks.into_iter().map(|t| t.article_id).collect()
+        };
+
+        let options = ListOptions {
+            tags: vec![tags[0]],
+            all_tags: false,
+        };
+        assert_eq!(articles(list(&db, user, options).await.unwrap()), vec![helm]);
+        let options = ListOptions {
+            tags: tags.clone(),
+            all_tags: true,
+        };
+        assert!(list(&db, user, options).await.unwrap().is_empty());
+        let options = ListOptions {
+            tags: vec![],
+            all_tags: false,
+        };
+        assert_eq!(list(&db, user, options).await.unwrap().len(), 2);
+    }
+}
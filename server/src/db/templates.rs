@@ -0,0 +1,140 @@
+use super::article::{self, ArticleError, ArticleInsert, ArticlePreview};
+use super::events::Events;
+use super::links::Analyzer as LinkAnalyzer;
+use super::Db;
+use crate::utils;
+use chrono::Local;
+use rocket::serde::Serialize;
+
+// ========================== TYPES =======================
+
+pub const TEMPLATE_TAG: &str = "template";
+/// Title of instances whose template title is only placeholders
+const DEFAULT_TITLE: &str = "Untitled {{date}}";
+
+// ========================== ERRORS ======================
+
+#[derive(Debug, thiserror::Error, Serialize)]
+pub enum TemplateError {
+    #[error("Not a template")]
+    NotTemplate,
+    #[error(transparent)]
+    Article(#[from] ArticleError),
+    #[error("Internal")]
+    Internal(
+        #[from]
+        #[source]
+        #[serde(skip)]
+        sqlx::Error,
+    ),
+}
+
+// ========================== FUNCTIONS ===================
+
+pub async fn list(db: &Db, user: i32) -> Result<Vec<ArticlePreview>, TemplateError> {
+    let templates = sqlx::query_as(
+        "
+        SELECT a.id, a.title, a.preview, a.notebook_id, a.created_on, a.updated_on,
+            ARRAY_REMOVE(ARRAY_AGG(t.name), NULL) as tags
+        FROM articles a
+            LEFT JOIN article_tags at ON at.article_id = a.id
+            LEFT JOIN tags t ON at.tag_id = t.id
+        WHERE a.user_id = $1
+        GROUP BY a.id
+        HAVING $2 = ANY(ARRAY_AGG(t.name))
+        ORDER BY a.title",
+    )
+    .bind(user)
+    .bind(TEMPLATE_TAG)
+    .fetch_all(db)
+    .await?;
+    Ok(article::decrypt_previews(db, user, templates).await?)
+}
+
+/// Copies the article with its tags and notebook
+pub async fn duplicate(
+    db: &Db,
+    link_analyzer: &LinkAnalyzer,
+    events: &Events,
+    user: i32,
+    id: i32,
+) -> Result<i32, TemplateError> {
+    let source = article::get(db, user, id).await?;
+    let title = format!("{} (copy)", source.title);
+    let insert = ArticleInsert {
+        id: None,
+        title: &title,
+        content: source.content,
+        tags: source.tags.iter().map(|t| t.as_str()).collect(),
+        language: source.language.as_deref(),
+        auto_tag: false,
+    };
+    let id = article::create(db, link_analyzer, events, user, insert).await?;
+    place_in_notebook(db, id, source.notebook_id).await?;
+    Ok(id)
+}
+
+/// Creates an article from the template, filling its placeholders.
+/// The title defaults to the filled template title.
+pub async fn instantiate(
+    db: &Db,
+    link_analyzer: &LinkAnalyzer,
+    events: &Events,
+    user: i32,
+    id: i32,
+    title: Option<&str>,
+) -> Result<i32, TemplateError> {
+    let template = article::get(db, user, id).await?;
+    if !template.tags.iter().any(|t| t == TEMPLATE_TAG) {
+        return Err(TemplateError::NotTemplate);
+    }
+    let email = super::user::email(db, user).await?.unwrap_or_default();
+    let date = Local::now().naive_local().format("%Y-%m-%d").to_string();
+    let title = match title.map(str::trim).filter(|t| !t.is_empty()) {
+        Some(title) => title.to_owned(),
+        None => {
+            let filled = utils::fill_placeholders(
+                &template.title,
+                &[("date", &date), ("user", &email), ("title", "")],
+            );
+            // placeholder-only titles would be empty
+            match filled.trim() {
+                "" => utils::fill_placeholders(DEFAULT_TITLE, &[("date", &date)]),
+                filled => filled.to_owned(),
+            }
+        }
+    };
+    let content = utils::fill_placeholders(
+        &template.content,
+        &[("date", &date), ("user", &email), ("title", &title)],
+    );
+    let insert = ArticleInsert {
+        id: None,
+        title: title.trim(),
+        content,
+        tags: template
+            .tags
+            .iter()
+            .map(|t| t.as_str())
+            .filter(|t| *t != TEMPLATE_TAG)
+            .collect(),
+        language: template.language.as_deref(),
+        auto_tag: false,
+    };
+    let id = article::create(db, link_analyzer, events, user, insert).await?;
+    place_in_notebook(db, id, template.notebook_id).await?;
+    Ok(id)
+}
+
+// ========================== HELPERS =====================
+
+async fn place_in_notebook(db: &Db, id: i32, notebook: Option<i32>) -> Result<(), sqlx::Error> {
+    if notebook.is_some() {
+        sqlx::query("UPDATE articles SET notebook_id = $2 WHERE id = $1")
+            .bind(id)
+            .bind(notebook)
+            .execute(db)
+            .await?;
+    }
+    Ok(())
+}
@@ -70,6 +70,13 @@ pub async fn login(db: &Db, email: &str, given_password: &str) -> Result<UserTok
     }
 }
 
+pub async fn email(db: &Db, id: UserId) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar("SELECT email FROM users WHERE id = $1")
+        .bind(id)
+        .fetch_optional(db)
+        .await
+}
+
 pub fn authenticate(token: &str) -> Option<UserId> {
     verify_token(token)
 }
@@ -0,0 +1,243 @@
+use super::article::{self, ArticleError};
+use super::Db;
+use crate::utils::frontmatter;
+use chrono::{DateTime, NaiveDateTime};
+use git2::{Commit, Oid, Repository, Signature, Sort};
+use once_cell::sync::OnceCell;
+use rocket::serde::{Deserialize, Serialize};
+use rocket::tokio;
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+// ========================== TYPES =======================
+
+const FILE_MODE: i32 = 0o100644;
+/// Commit signature of users without e-mail
+const FALLBACK_NAME: &str = "articles";
+const FALLBACK_EMAIL: &str = "articles@localhost";
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub enabled: bool,
+    pub path: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            enabled: false,
+            path: "versions".to_owned(),
+        }
+    }
+}
+
+/// Article change to commit
+#[derive(Debug, Clone, Copy)]
+pub enum Change {
+    Created,
+    Updated,
+    Deleted,
+}
+
+/// Repository root, commits are serialized
+struct Store {
+    root: PathBuf,
+    lock: Mutex<()>,
+}
+
+static STORE: OnceCell<Store> = OnceCell::new();
+
+#[derive(Debug, Serialize)]
+pub struct Version {
+    pub commit: String,
+    pub message: String,
+    pub author: String,
+    pub time: NaiveDateTime,
+}
+
+// ========================== ERRORS ======================
+
+#[derive(Debug, thiserror::Error, Serialize)]
+pub enum VersionError {
+    #[error("Version store disabled")]
+    Disabled,
+    #[error(transparent)]
+    Article(#[from] ArticleError),
+    #[error("Internal")]
+    Git(
+        #[from]
+        #[source]
+        #[serde(skip)]
+        git2::Error,
+    ),
+}
+
+// ========================== FUNCTIONS ===================
+
+/// Enables commits of article changes to a bare repository per user
+pub fn init(config: &Config) {
+    log::info!("Starting git version store in {}", config.path);
+    STORE
+        .set(Store {
+            root: PathBuf::from(&config.path),
+            lock: Mutex::new(()),
+        })
+        .ok();
+}
+
+/// Commits the current version of the article if the store is enabled.
+/// Called in the article write path, failures are logged.
+pub async fn commit(db: &Db, user: i32, id: i32, change: Change) {
+    let store = match STORE.get() {
+        Some(store) => store,
+        None => return,
+    };
+    if let Err(e) = store_version(db, store, user, id, change).await {
+        log::error!("Failed to store version of article {}: {:?}", id, e);
+    }
+}
+
+/// Lists commits that changed the article, newest first
+pub async fn history(
+    db: &Db,
+    config: &Config,
+    user: i32,
+    id: i32,
+) -> Result<Vec<Version>, VersionError> {
+    if !config.enabled {
+        return Err(VersionError::Disabled);
+    }
+    // check access
+    article::get(db, user, id).await?;
+    let repo_path = repo_path(Path::new(&config.path), user);
+    let versions = tokio::task::spawn_blocking(move || read_history(&repo_path, id))
+        .await
+        .expect("History task panicked")?;
+    Ok(versions)
+}
+
+// ========================== HELPERS =====================
+
+async fn store_version(
+    db: &Db,
+    store: &'static Store,
+    user: i32,
+    id: i32,
+    change: Change,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (content, message) = match change {
+        Change::Created | Change::Updated => {
+            let article = article::get(db, user, id).await?;
+            let content =
+                frontmatter::render(article.id, &article.title, &article.tags, &article.content);
+            let verb = match change {
+                Change::Created => "Create",
+                _ => "Update",
+            };
+            (Some(content), format!("{} {}", verb, article.title))
+        }
+        Change::Deleted => (None, format!("Delete article {}", id)),
+    };
+    let email = super::user::email(db, user).await?.filter(|e| !e.is_empty());
+    let repo_path = repo_path(&store.root, user);
+    tokio::task::spawn_blocking(move || {
+        let _guard = store.lock.lock().unwrap();
+        commit_file(&repo_path, &file_name(id), content.as_deref(), email.as_deref(), &message)
+    })
+    .await??;
+    Ok(())
+}
+
+fn repo_path(root: &Path, user: i32) -> PathBuf {
+    root.join(format!("{}.git", user))
+}
+
+fn file_name(id: i32) -> String {
+    format!("{}.md", id)
+}
+
+fn open_repo(path: &Path) -> Result<Repository, git2::Error> {
+    match Repository::open_bare(path) {
+        Ok(repo) => Ok(repo),
+        Err(_) => Repository::init_bare(path),
+    }
+}
+
+/// Writes or removes (if content is None) the file in a new commit on HEAD
+fn commit_file(
+    repo_path: &Path,
+    file: &str,
+    content: Option<&str>,
+    email: Option<&str>,
+    message: &str,
+) -> Result<(), git2::Error> {
+    let repo = open_repo(repo_path)?;
+    let parent = match repo.head() {
+        Ok(head) => Some(head.peel_to_commit()?),
+        Err(_) => None,
+    };
+    let parent_tree = parent.as_ref().map(|c| c.tree()).transpose()?;
+    let mut builder = repo.treebuilder(parent_tree.as_ref())?;
+    match content {
+        Some(content) => {
+            let blob = repo.blob(content.as_bytes())?;
+            builder.insert(file, blob, FILE_MODE)?;
+        }
+        None if builder.get(file)?.is_some() => builder.remove(file)?,
+        None => return Ok(()),
+    }
+    let tree_id = builder.write()?;
+    // nothing changed
+    if parent_tree.map(|t| t.id()) == Some(tree_id) {
+        return Ok(());
+    }
+    let tree = repo.find_tree(tree_id)?;
+    let signature = match email {
+        Some(email) => Signature::now(email, email)?,
+        None => Signature::now(FALLBACK_NAME, FALLBACK_EMAIL)?,
+    };
+    let parents: Vec<&Commit> = parent.iter().collect();
+    repo.commit(Some("HEAD"), &signature, &signature, message, &tree, &parents)?;
+    Ok(())
+}
+
+fn read_history(repo_path: &Path, id: i32) -> Result<Vec<Version>, git2::Error> {
+    let repo = match Repository::open_bare(repo_path) {
+        Ok(repo) => repo,
+        Err(_) => return Ok(vec![]),
+    };
+    if repo.head().is_err() {
+        return Ok(vec![]);
+    }
+    let file = file_name(id);
+    let entry_id = |commit: &Commit| -> Result<Option<Oid>, git2::Error> {
+        Ok(commit.tree()?.get_name(&file).map(|e| e.id()))
+    };
+    let mut walk = repo.revwalk()?;
+    walk.set_sorting(Sort::TIME)?;
+    walk.push_head()?;
+    let mut versions = Vec::new();
+    for oid in walk {
+        let commit = repo.find_commit(oid?)?;
+        let current = entry_id(&commit)?;
+        let previous = match commit.parent(0) {
+            Ok(parent) => entry_id(&parent)?,
+            Err(_) => None,
+        };
+        if current == previous {
+            continue;
+        }
+        versions.push(Version {
+            commit: commit.id().to_string(),
+            message: commit.message().unwrap_or_default().to_owned(),
+            author: commit.author().email().unwrap_or_default().to_owned(),
+            time: DateTime::from_timestamp(commit.time().seconds(), 0)
+                .expect("Commit time is in range")
+                .naive_utc(),
+        });
+    }
+    Ok(versions)
+}
@@ -0,0 +1,458 @@
+use super::events::{Event, EventKind, Events, EVENT_NAMES};
+use super::Db;
+use chrono::{NaiveDateTime, Utc};
+use hmac::{Hmac, Mac, NewMac};
+use rand::{distributions::Alphanumeric, Rng};
+use rocket::serde::{json, Deserialize, Serialize};
+use rocket::tokio::{self, sync::broadcast::error::RecvError};
+use sha2::Sha256;
+use std::{sync::Arc, time::Duration};
+
+// ========================== TYPES =======================
+
+const SECRET_LEN: usize = 32;
+/// Deliveries claimed at once
+const BATCH_SIZE: i64 = 50;
+/// Retries are picked up at least this often
+const POLL_MS: u64 = 1000;
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub max_attempts: i32,
+    pub backoff_ms: u64,
+    pub timeout_ms: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            max_attempts: 5,
+            backoff_ms: 1000,
+            timeout_ms: 10000,
+        }
+    }
+}
+
+impl Config {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_attempts < 1 {
+            return Err(format!(
+                "Webhook max_attempts must be at least 1, got {}",
+                self.max_attempts
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, sqlx::FromRow, Serialize)]
+pub struct Webhook {
+    pub id: i32,
+    pub url: String,
+    pub events: Vec<String>,
+    pub active: bool,
+    pub created_on: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WebhookCreated {
+    pub id: i32,
+    pub secret: String,
+}
+
+#[derive(Deserialize)]
+pub struct WebhookInsert {
+    pub url: String,
+    pub events: Vec<String>,
+    pub secret: Option<String>,
+}
+
+#[derive(Debug, sqlx::FromRow, Serialize)]
+pub struct Delivery {
+    pub id: i32,
+    pub event: String,
+    pub payload: String,
+    pub attempts: i32,
+    pub success: bool,
+    pub status: Option<i32>,
+    pub error: Option<String>,
+    pub created_on: NaiveDateTime,
+    pub delivered_on: Option<NaiveDateTime>,
+}
+
+/// Claimed delivery with its webhook
+#[derive(Debug, sqlx::FromRow)]
+struct Pending {
+    id: i32,
+    event: String,
+    payload: String,
+    url: String,
+    secret: String,
+}
+
+#[derive(Serialize)]
+struct Payload<'a> {
+    #[serde(flatten)]
+    kind: &'a EventKind,
+    user: i32,
+    timestamp: NaiveDateTime,
+}
+
+struct Dispatcher {
+    db: Arc<Db>,
+    client: reqwest::Client,
+    config: Config,
+}
+
+// ========================== ERRORS ======================
+
+#[derive(Debug, thiserror::Error, Serialize)]
+pub enum WebhookError {
+    #[error("Not found")]
+    NotFound,
+    #[error("Bad url")]
+    BadUrl,
+    #[error("Unknown event")]
+    UnknownEvent,
+    #[error("Internal")]
+    Internal(
+        #[from]
+        #[source]
+        #[serde(skip)]
+        sqlx::Error,
+    ),
+}
+
+// ========================== FUNCTIONS ===================
+
+pub async fn list(db: &Db, user: i32) -> Result<Vec<Webhook>, WebhookError> {
+    Ok(sqlx::query_as(
+        "SELECT id, url, events, active, created_on FROM webhooks
+        WHERE user_id = $1
+        ORDER BY id",
+    )
+    .bind(user)
+    .fetch_all(db)
+    .await?)
+}
+
+/// Creates the subscription, the secret is generated if not given
+pub async fn create(
+    db: &Db,
+    user: i32,
+    webhook: WebhookInsert,
+) -> Result<WebhookCreated, WebhookError> {
+    if !(webhook.url.starts_with("http://") || webhook.url.starts_with("https://")) {
+        return Err(WebhookError::BadUrl);
+    }
+    if webhook.events.is_empty()
+        || !webhook
+            .events
+            .iter()
+            .all(|e| EVENT_NAMES.contains(&e.as_str()))
+    {
+        return Err(WebhookError::UnknownEvent);
+    }
+    let secret = webhook.secret.unwrap_or_else(generate_secret);
+    let id = sqlx::query_scalar(
+        "INSERT INTO webhooks (user_id, url, secret, events) VALUES ($1, $2, $3, $4)
+        RETURNING id",
+    )
+    .bind(user)
+    .bind(webhook.url)
+    .bind(&secret)
+    .bind(webhook.events)
+    .fetch_one(db)
+    .await?;
+    Ok(WebhookCreated { id, secret })
+}
+
+pub async fn delete(db: &Db, user: i32, id: i32) -> Result<(), WebhookError> {
+    let result = sqlx::query("DELETE FROM webhooks WHERE id = $1 AND user_id = $2")
+        .bind(id)
+        .bind(user)
+        .execute(db)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(WebhookError::NotFound);
+    }
+    Ok(())
+}
+
+pub async fn deliveries(
+    db: &Db,
+    user: i32,
+    id: i32,
+    (offset, limit): (u32, u32),
+) -> Result<Vec<Delivery>, WebhookError> {
+    let exists: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM webhooks WHERE id = $1 AND user_id = $2)",
+    )
+    .bind(id)
+    .bind(user)
+    .fetch_one(db)
+    .await?;
+    if !exists {
+        return Err(WebhookError::NotFound);
+    }
+    Ok(sqlx::query_as(
+        "
+        SELECT id, event, payload, attempts, success, status, error, created_on, delivered_on
+        FROM webhook_deliveries
+        WHERE webhook_id = $1
+        ORDER BY id DESC
+        LIMIT $2 OFFSET $3",
+    )
+    .bind(id)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(db)
+    .await?)
+}
+
+/// Stores a delivery of the event for each matching webhook of the user
+pub async fn enqueue(db: &Db, event: &Event) -> Result<(), sqlx::Error> {
+    let payload = json::serde_json::to_string(&Payload {
+        kind: &event.kind,
+        user: event.user,
+        timestamp: Utc::now().naive_utc(),
+    })
+    .expect("Failed to serialize event");
+    sqlx::query(
+        "INSERT INTO webhook_deliveries (webhook_id, event, payload)
+        SELECT id, $2, $3 FROM webhooks
+        WHERE user_id = $1 AND active AND $2 = ANY(events)",
+    )
+    .bind(event.user)
+    .bind(event.kind.name())
+    .bind(payload)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Delivers stored deliveries in the background.
+/// Events only wake the dispatcher, the table holds the queue.
+pub fn start_dispatcher(db: Arc<Db>, events: &Events, config: Config) {
+    log::info!("Starting webhook dispatcher");
+    let mut recv = events.subscribe();
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_millis(config.timeout_ms))
+        .build()
+        .expect("Failed to build webhook client");
+    let dispatcher = Arc::new(Dispatcher { db, client, config });
+    tokio::spawn(async move {
+        loop {
+            if let Err(e) = dispatch(&dispatcher).await {
+                log::warn!("Failed to dispatch webhooks: {:?}", e);
+            }
+            tokio::select! {
+                received = recv.recv() => {
+                    if let Err(RecvError::Closed) = received {
+                        break;
+                    }
+                }
+                _ = tokio::time::sleep(Duration::from_millis(POLL_MS)) => (),
+            }
+        }
+        log::debug!("Webhook dispatcher stopped");
+    });
+}
+
+/// Hex encoded HMAC-SHA256 of the body
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(body);
+    format!("{:x}", mac.finalize().into_bytes())
+}
+
+// ========================== HELPERS =====================
+
+/// Claims due deliveries until none are left and delivers them concurrently
+async fn dispatch(dispatcher: &Arc<Dispatcher>) -> Result<(), sqlx::Error> {
+    loop {
+        let claimed = claim(dispatcher).await?;
+        let done = (claimed.len() as i64) < BATCH_SIZE;
+        for pending in claimed {
+            let dispatcher = dispatcher.clone();
+            tokio::spawn(async move {
+                deliver(&dispatcher, pending).await;
+            });
+        }
+        if done {
+            return Ok(());
+        }
+    }
+}
+
+/// Counts an attempt for due deliveries and schedules their retry with
+/// exponential backoff, after the request timeout
+async fn claim(dispatcher: &Dispatcher) -> Result<Vec<Pending>, sqlx::Error> {
+    let config = &dispatcher.config;
+    sqlx::query_as(
+        "
+        UPDATE webhook_deliveries d SET
+        attempts = d.attempts + 1,
+        next_attempt_on = CASE WHEN d.attempts + 1 < $1
+            THEN LOCALTIMESTAMP
+                + make_interval(secs => ($2::float8 * 2 ^ d.attempts + $3::float8) / 1000)
+            ELSE NULL
+        END
+        FROM webhooks w
+        WHERE w.id = d.webhook_id AND d.id IN (
+            SELECT id FROM webhook_deliveries
+            WHERE next_attempt_on <= LOCALTIMESTAMP
+            ORDER BY id
+            LIMIT $4
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING d.id, d.event, d.payload, w.url, w.secret",
+    )
+    .bind(config.max_attempts)
+    .bind(config.backoff_ms as i64)
+    .bind(config.timeout_ms as i64)
+    .bind(BATCH_SIZE)
+    .fetch_all(dispatcher.db.as_ref())
+    .await
+}
+
+async fn deliver(dispatcher: &Dispatcher, pending: Pending) {
+    let (status, error) = post(&dispatcher.client, &pending).await;
+    let success = error.is_none();
+    let updated = sqlx::query(
+        "
+        UPDATE webhook_deliveries SET
+        success = $2, status = $3, error = $4,
+        delivered_on = CASE WHEN $2 THEN CURRENT_TIMESTAMP ELSE NULL END,
+        next_attempt_on = CASE WHEN $2 THEN NULL ELSE next_attempt_on END
+        WHERE id = $1",
+    )
+    .bind(pending.id)
+    .bind(success)
+    .bind(status)
+    .bind(error)
+    .execute(dispatcher.db.as_ref())
+    .await;
+    if let Err(e) = updated {
+        log::warn!("Failed to update webhook delivery {}: {:?}", pending.id, e);
+    }
+}
+
+/// Posts the signed payload, returns the response status and the error if it failed
+async fn post(client: &reqwest::Client, pending: &Pending) -> (Option<i32>, Option<String>) {
+    let signature = sign(&pending.secret, pending.payload.as_bytes());
+    let response = client
+        .post(&pending.url)
+        .header("Content-Type", "application/json")
+        .header("X-Webhook-Event", &pending.event)
+        .header("X-Webhook-Delivery", pending.id.to_string())
+        .header("X-Webhook-Signature", format!("sha256={}", signature))
+        .body(pending.payload.clone())
+        .send()
+        .await;
+    match response {
+        Ok(response) if response.status().is_success() => {
+            (Some(response.status().as_u16() as i32), None)
+        }
+        Ok(response) => (
+            Some(response.status().as_u16() as i32),
+            Some(format!("Unexpected status {}", response.status())),
+        ),
+        Err(e) => (None, Some(e.to_string())),
+    }
+}
+
+fn generate_secret() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(SECRET_LEN)
+        .map(char::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use rocket::tokio::net::TcpListener;
+
+    #[test]
+    fn signature_is_hex_hmac_sha256() {
+        assert_eq!(
+            sign("key", b"The quick brown fox jumps over the lazy dog"),
+            "f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+    }
+
+    /// Accepts one request, answers with the status and returns the raw request
+    async fn listen(status: &'static str) -> (String, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let handle = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = String::new();
+            let mut buf = [0u8; 4096];
+            while !is_complete(&request) {
+                let read = socket.read(&mut buf).await.unwrap();
+                if read == 0 {
+                    break;
+                }
+                request.push_str(std::str::from_utf8(&buf[..read]).unwrap());
+            }
+            let response = format!("HTTP/1.1 {}\r\nContent-Length: 0\r\n\r\n", status);
+            socket.write_all(response.as_bytes()).await.unwrap();
+            request
+        });
+        (url, handle)
+    }
+
+    fn is_complete(request: &str) -> bool {
+        let (head, body) = match request.split_once("\r\n\r\n") {
+            Some(parts) => parts,
+            None => return false,
+        };
+        let length = head
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+            .and_then(|(_, value)| value.trim().parse().ok())
+            .unwrap_or(0);
+        body.len() >= length
+    }
+
+    fn pending(url: String) -> Pending {
+        Pending {
+            id: 7,
+            event: "article.created".to_owned(),
+            payload: r#"{"event":"article.created","data":{"id":1}}"#.to_owned(),
+            url,
+            secret: "secret".to_owned(),
+        }
+    }
+
+    #[rocket::async_test]
+    async fn delivers_signed_payload() {
+        let (url, handle) = listen("200 OK").await;
+        let pending = pending(url);
+        let (status, error) = post(&reqwest::Client::new(), &pending).await;
+        assert_eq!((status, error), (Some(200), None));
+        let request = handle.await.unwrap().to_lowercase();
+        assert!(request.starts_with("post /hook "));
+        assert!(request.contains("x-webhook-event: article.created"));
+        assert!(request.contains("x-webhook-delivery: 7"));
+        let signature = sign(&pending.secret, pending.payload.as_bytes());
+        assert!(request.contains(&format!("x-webhook-signature: sha256={}", signature)));
+        assert!(request.ends_with(&pending.payload.to_lowercase()));
+    }
+
+    #[rocket::async_test]
+    async fn reports_failed_delivery() {
+        let (url, handle) = listen("500 Internal Server Error").await;
+        let (status, error) = post(&reqwest::Client::new(), &pending(url)).await;
+        handle.await.unwrap();
+        assert_eq!(status, Some(500));
+        assert!(error.is_some());
+    }
+}
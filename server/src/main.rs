@@ -30,12 +30,66 @@ async fn run() {
         Arc::new(db)
     };
 
+    // Init language detection, with the text search configs of the database
+    {
+        let config: utils::LanguageConfig = if figment.contains("language") {
+            figment
+                .extract_inner("language")
+                .expect("No valid language config found")
+        } else {
+            Default::default()
+        };
+        let installed = db::text_search_configs(&db)
+            .await
+            .expect("Failed to read text search configs");
+        utils::init_language(config, installed).expect("Invalid language config");
+    }
+
+    // Init encryption at rest
+    {
+        let config: db::crypto::Config = if figment.contains("encryption") {
+            figment
+                .extract_inner("encryption")
+                .expect("No valid encryption config found")
+        } else {
+            Default::default()
+        };
+        // re-wraps the data keys, the new key has to be configured afterwards
+        let args: Vec<String> = std::env::args().collect();
+        if args.get(1).map(String::as_str) == Some("rotate-master-key") {
+            let old_key = config.master_key.as_deref().expect("No master key configured");
+            let new_key = args.get(2).expect("Usage: rotate-master-key <new key>");
+            let rotated = db::crypto::rotate_master_key(&db, old_key, new_key)
+                .await
+                .expect("Failed to rotate master key");
+            log::info!("Rotated master key for {} users", rotated);
+            return;
+        }
+        db::crypto::init(config).expect("Invalid encryption config");
+    }
+
+    // Init events
+    let events = Arc::new(db::events::Events::new(db.clone()));
+
+    // Init webhook dispatcher
+    {
+        let config: db::webhooks::Config = if figment.contains("webhooks") {
+            figment
+                .extract_inner("webhooks")
+                .expect("No valid webhooks config found")
+        } else {
+            Default::default()
+        };
+        config.validate().expect("Invalid webhooks config");
+        db::webhooks::start_dispatcher(db.clone(), &events, config);
+    }
+
     // Init link analyzer
     let link_analyzer = {
         let config: db::links::AnalyzerConfig = figment
             .extract_inner("selenium")
             .expect("No valid selenium config found");
-        let analyzer = db::links::start_analyzer(db.clone(), config)
+        let analyzer = db::links::start_analyzer(db.clone(), events.clone(), config)
             .await
             .expect("Failed to start link analyzer");
         db::links::analyze_fresh(&analyzer)
@@ -44,17 +98,71 @@ async fn run() {
         Arc::new(analyzer)
     };
 
+    // Init filesystem mirror
+    {
+        let config: db::mirror::Config = if figment.contains("mirror") {
+            figment
+                .extract_inner("mirror")
+                .expect("No valid mirror config found")
+        } else {
+            Default::default()
+        };
+        // the mirror writes decrypted articles to disk
+        if config.enabled && db::crypto::is_enabled() {
+            panic!("The filesystem mirror can't be enabled with encryption at rest");
+        }
+        if config.enabled {
+            db::mirror::start_mirror(db.clone(), &link_analyzer, events.clone(), config)
+                .await
+                .expect("Failed to start filesystem mirror");
+        }
+    }
+
+    // Init git version store
+    let versions = {
+        let config: db::versions::Config = if figment.contains("git") {
+            figment
+                .extract_inner("git")
+                .expect("No valid git config found")
+        } else {
+            Default::default()
+        };
+        // the repositories hold decrypted articles
+        if config.enabled && db::crypto::is_enabled() {
+            panic!("The git version store can't be enabled with encryption at rest");
+        }
+        if config.enabled {
+            db::versions::init(&config);
+        }
+        Arc::new(config)
+    };
+
     let security = {
         let config: security::Config = figment.extract_inner("security")
             .expect("Failed to parse security");
         Arc::new(config)
     };
 
+    let daily = {
+        let config: db::daily::Config = if figment.contains("daily") {
+            figment
+                .extract_inner("daily")
+                .expect("Failed to parse daily")
+        } else {
+            Default::default()
+        };
+        config.validate().expect("Invalid daily config");
+        Arc::new(config)
+    };
+
     // register state
     let rocket = rocket
         .manage(db)
         .manage(link_analyzer.clone())
-        .manage(security);
+        .manage(events)
+        .manage(security)
+        .manage(daily)
+        .manage(versions);
 
     // register routes
     let rocket = rocket.mount(
@@ -62,12 +170,53 @@ async fn run() {
         routes![
             api::user::info,
             api::user::login,
+            api::versions::history,
             api::article::list,
             api::article::get,
+            api::article::suggested_tags,
             api::article::update,
             api::article::delete,
+            api::daily::get,
+            api::daily::calendar,
+            api::events::stream,
+            api::notebooks::list,
+            api::notebooks::create,
+            api::notebooks::rename,
+            api::notebooks::move_notebook,
+            api::notebooks::delete,
+            api::notebooks::move_article,
+            api::review::enable,
+            api::review::disable,
+            api::review::due,
+            api::review::grade,
+            api::revisions::list,
+            api::revisions::diff,
+            api::searches::list,
+            api::searches::get,
+            api::searches::articles,
+            api::searches::update,
+            api::searches::delete,
+            api::sync::changes,
+            api::sync::push,
             api::tags::list,
-            api::links::list
+            api::tags::suggest,
+            api::tags::graph,
+            api::tags::update,
+            api::tags::delete,
+            api::tags::merge,
+            api::tags::aliases,
+            api::tags::create_alias,
+            api::tags::delete_alias,
+            api::tasks::list,
+            api::tasks::toggle,
+            api::templates::list,
+            api::templates::duplicate,
+            api::templates::instantiate,
+            api::links::list,
+            api::webhooks::list,
+            api::webhooks::create,
+            api::webhooks::delete,
+            api::webhooks::deliveries
         ],
     );
 
@@ -0,0 +1,121 @@
+use rocket::serde::Serialize;
+use similar::{ChangeTag, TextDiff};
+
+const CONTEXT_LINES: usize = 3;
+
+#[derive(Debug, Serialize)]
+pub struct Hunk {
+    pub from_start: usize,
+    pub from_len: usize,
+    pub to_start: usize,
+    pub to_len: usize,
+    pub lines: Vec<Line>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Line {
+    pub tag: &'static str,
+    pub content: String,
+    /// Word level segments, changed words are emphasized
+    pub words: Vec<Word>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Word {
+    pub emphasized: bool,
+    pub value: String,
+}
+
+/// Line diff of the two texts grouped into hunks with context
+pub fn diff_lines(from: &str, to: &str) -> Vec<Hunk> {
+    let diff = TextDiff::from_lines(from, to);
+    diff.grouped_ops(CONTEXT_LINES)
+        .iter()
+        .filter_map(|group| {
+            let (first, last) = (group.first()?, group.last()?);
+            let from_range = first.old_range().start..last.old_range().end;
+            let to_range = first.new_range().start..last.new_range().end;
+            let lines = group
+                .iter()
+                .flat_map(|op| diff.iter_inline_changes(op))
+                .map(|change| {
+                    let mut words: Vec<Word> = Vec::new();
+                    for (emphasized, value) in change.values() {
+                        let value = value.to_string();
+                        match words.last_mut() {
+                            Some(word) if word.emphasized == *emphasized => {
+                                word.value.push_str(&value)
+                            }
+                            _ => words.push(Word {
+                                emphasized: *emphasized,
+                                value,
+                            }),
+                        }
+                    }
+                    Line {
+                        tag: tag_name(change.tag()),
+                        content: words.iter().map(|w| w.value.as_str()).collect(),
+                        words,
+                    }
+                })
+                .collect();
+            Some(Hunk {
+                from_start: from_range.start + 1,
+                from_len: from_range.len(),
+                to_start: to_range.start + 1,
+                to_len: to_range.len(),
+                lines,
+            })
+        })
+        .collect()
+}
+
+fn tag_name(tag: ChangeTag) -> &'static str {
+    match tag {
+        ChangeTag::Equal => "equal",
+        ChangeTag::Insert => "insert",
+        ChangeTag::Delete => "delete",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equal_texts_have_no_hunks() {
+        assert!(diff_lines("a\nb\n", "a\nb\n").is_empty());
+    }
+
+    #[test]
+    fn changed_line_is_grouped_with_context() {
+        let from = "1\n2\n3\n4\n5\n6\n7\n8\n9\n";
+        let to = "1\n2\n3\n4\nfive\n6\n7\n8\n9\n";
+        let hunks = diff_lines(from, to);
+        assert_eq!(hunks.len(), 1);
+        let hunk = &hunks[0];
+        assert_eq!((hunk.from_start, hunk.from_len), (2, 7));
+        assert_eq!((hunk.to_start, hunk.to_len), (2, 7));
+        let tags: Vec<&str> = hunk.lines.iter().map(|l| l.tag).collect();
+        assert_eq!(
+            tags,
+            vec!["equal", "equal", "equal", "delete", "insert", "equal", "equal", "equal"]
+        );
+        assert_eq!(hunk.lines[3].content, "5\n");
+        assert_eq!(hunk.lines[4].content, "five\n");
+    }
+
+    #[test]
+    fn changed_words_are_emphasized() {
+        let hunks = diff_lines("the quick fox\n", "the slow fox\n");
+        let insert = hunks[0].lines.iter().find(|l| l.tag == "insert").unwrap();
+        let emphasized: Vec<&str> = insert
+            .words
+            .iter()
+            .filter(|w| w.emphasized)
+            .map(|w| w.value.as_str())
+            .collect();
+        assert_eq!(emphasized, vec!["slow"]);
+        assert_eq!(insert.content, "the slow fox\n");
+    }
+}
@@ -1,38 +1,76 @@
 use comrak::{
     nodes::{AstNode, NodeLink, NodeValue},
-    parse_document, Arena, ComrakOptions,
+    parse_document, Arena, ComrakExtensionOptions, ComrakOptions,
 };
-use std::cell::RefCell;
+use std::{cell::RefCell, collections::HashSet};
 
 const MAX_PREVIEW_LEN: usize = 160;
 pub struct Info {
     pub preview: String,
     pub text: String,
     pub links: Vec<String>,
+    pub tasks: Vec<Task>,
     pub language: &'static str,
 }
 
-pub fn extract_article(content: &str) -> Info {
+pub struct Task {
+    pub text: String,
+    pub checked: bool,
+    pub line: i32,
+}
+
+fn parse_options() -> ComrakOptions {
+    ComrakOptions {
+        extension: ComrakExtensionOptions {
+            strikethrough: true,
+            table: true,
+            autolink: true,
+            tasklist: true,
+            footnotes: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+pub fn extract_article(content: &str, language: Option<&'static str>) -> Info {
     let arena = Arena::new();
-    let root = parse_document(&arena, content, &ComrakOptions::default());
+    let options = parse_options();
+    let root = parse_document(&arena, content, &options);
 
     let text = RefCell::new(Vec::new());
     let links = RefCell::new(Vec::new());
-    iter_nodes(root, &|node| match &mut node.data.borrow_mut().value {
-        NodeValue::Text(ref entry) => {
-            if let Ok(entry) = String::from_utf8(entry.to_owned()) {
-                text.borrow_mut().push(entry);
+    let tasks = RefCell::new(Vec::new());
+    iter_nodes(root, &|node| {
+        let ast = node.data.borrow();
+        match &ast.value {
+            NodeValue::Text(ref entry) => {
+                if let Ok(entry) = String::from_utf8(entry.to_owned()) {
+                    text.borrow_mut().push(entry);
+                }
             }
-        }
-        NodeValue::Link(NodeLink { ref url, ref title }) => {
-            if let Ok(link) = String::from_utf8(url.to_owned()) {
-                links.borrow_mut().push(link);
+            NodeValue::Link(NodeLink { ref url, ref title }) => {
+                if let Ok(link) = String::from_utf8(url.to_owned()) {
+                    links.borrow_mut().push(link);
+                }
+                if let Ok(entry) = String::from_utf8(title.to_owned()) {
+                    text.borrow_mut().push(entry);
+                }
             }
-            if let Ok(entry) = String::from_utf8(title.to_owned()) {
-                text.borrow_mut().push(entry);
+            NodeValue::Image(NodeLink { ref url, .. }) => {
+                if let Ok(link) = String::from_utf8(url.to_owned()) {
+                    links.borrow_mut().push(link);
+                }
             }
+            NodeValue::TaskItem(checked) => {
+                tasks.borrow_mut().push(Task {
+                    text: task_text(node),
+                    checked: *checked,
+                    line: ast.start_line as i32,
+                });
+            }
+            _ => (),
         }
-        _ => (),
     });
     let preview = text
         .borrow()
@@ -47,15 +85,98 @@ pub fn extract_article(content: &str) -> Info {
             preview
         });
     let text = text.take().join(" ");
-    let language = super::detect_language(&text);
+    // unused reference definitions are dropped by the parser
+    let links = {
+        let mut links = links.take();
+        links.extend(reference_links(content, &options));
+        let mut seen = HashSet::new();
+        links.retain(|link| !link.is_empty() && seen.insert(link.clone()));
+        links
+    };
+    let language = language.unwrap_or_else(|| super::detect_language(&text));
     Info {
         preview,
         text,
-        links: links.take(),
+        links,
+        tasks: tasks.take(),
         language,
     }
 }
 
+/// Rewrites the task checkbox on the given line (1-based) of the content.
+/// Returns None if the line holds no checkbox.
+pub fn set_task_checked(content: &str, line: i32, checked: bool) -> Option<String> {
+    let mut lines: Vec<String> = content.split('\n').map(|l| l.to_owned()).collect();
+    let target = lines.get_mut((line as usize).checked_sub(1)?)?;
+    let pos = ["[ ]", "[x]", "[X]"]
+        .iter()
+        .filter_map(|checkbox| target.find(checkbox))
+        .min()?;
+    let checkbox = if checked { "[x]" } else { "[ ]" };
+    target.replace_range(pos..pos + 3, checkbox);
+    Some(lines.join("\n"))
+}
+
+/// Collects http(s) urls of reference-style link definitions like `[label]: https://...`.
+/// The parser drops unused definitions, so the content is parsed again behind
+/// a paragraph referencing every label and the resolved links are read back.
+fn reference_links(content: &str, options: &ComrakOptions) -> Vec<String> {
+    let usages: String = content
+        .lines()
+        .filter_map(|line| line.trim_start().strip_prefix('[')?.split_once("]:"))
+        .map(|(label, _)| label)
+        .filter(|label| !label.trim().is_empty() && !label.starts_with('^'))
+        .map(|label| format!("[{}]\n", label))
+        .collect();
+    if usages.is_empty() {
+        return vec![];
+    }
+    let arena = Arena::new();
+    let root = parse_document(&arena, &format!("{}\n{}", usages, content), options);
+    let usages = match root.first_child() {
+        Some(usages) => usages,
+        None => return vec![],
+    };
+    let links = RefCell::new(Vec::new());
+    iter_nodes(usages, &|node| {
+        if let NodeValue::Link(NodeLink { ref url, .. }) = node.data.borrow().value {
+            if let Ok(link) = String::from_utf8(url.to_owned()) {
+                links.borrow_mut().push(link);
+            }
+        }
+    });
+    links
+        .take()
+        .into_iter()
+        .filter(|link| match link.split_once("://") {
+            Some((scheme, _)) => {
+                scheme.eq_ignore_ascii_case("http") || scheme.eq_ignore_ascii_case("https")
+            }
+            None => false,
+        })
+        .collect()
+}
+
+fn task_text<'a>(node: &'a AstNode<'a>) -> String {
+    let parts = RefCell::new(Vec::new());
+    for child in node.children() {
+        // skip nested lists, they hold their own tasks
+        if let NodeValue::List(_) = child.data.borrow().value {
+            continue;
+        }
+        iter_nodes(child, &|n| {
+            if let NodeValue::Text(ref entry) = n.data.borrow().value {
+                if let Ok(entry) = std::str::from_utf8(entry) {
+                    parts.borrow_mut().push(entry.trim().to_owned());
+                }
+            }
+        });
+    }
+    let mut parts = parts.take();
+    parts.retain(|p| !p.is_empty());
+    parts.join(" ")
+}
+
 fn iter_nodes<'a, F>(node: &'a AstNode<'a>, f: &F)
 where
     F: Fn(&'a AstNode<'a>),
@@ -65,3 +186,65 @@ where
         iter_nodes(c, f);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reference_links_are_collected() {
+        let content = concat!(
+            "Text\n\n",
+            "[docs]: https://docs.rs/comrak \"Docs\"\n",
+            "  [home]: <http://example.com>\n",
+        );
+        assert_eq!(
+            reference_links(content, &parse_options()),
+            vec!["https://docs.rs/comrak", "http://example.com"]
+        );
+    }
+
+    #[test]
+    fn reference_links_skip_code_blocks() {
+        let content = concat!(
+            "```\n[a]: https://a.com\n```\n\n",
+            "    [b]: https://b.com\n\n",
+            "~~~~md\n[c]: https://c.com\n~~~\n[d]: https://d.com\n~~~~\n",
+            "[e]: https://e.com\n",
+        );
+        assert_eq!(reference_links(content, &parse_options()), vec!["https://e.com"]);
+    }
+
+    #[test]
+    fn reference_links_require_http() {
+        let content = concat!(
+            "[a]: word\n\n",
+            "[b]: mailto:me@example.com\n\n",
+            "[^note]: https://n.com\n\n",
+            "[]: https://x.com\n\n",
+            "[c]: HTTPS://C.COM\n",
+        );
+        assert_eq!(reference_links(content, &parse_options()), vec!["HTTPS://C.COM"]);
+    }
+
+    #[test]
+    fn task_is_checked_and_unchecked() {
+        let content = "# Todo\n- [ ] first\n- [X] second";
+        assert_eq!(
+            set_task_checked(content, 2, true).as_deref(),
+            Some("# Todo\n- [x] first\n- [X] second")
+        );
+        assert_eq!(
+            set_task_checked(content, 3, false).as_deref(),
+            Some("# Todo\n- [ ] first\n- [ ] second")
+        );
+    }
+
+    #[test]
+    fn task_line_without_checkbox() {
+        let content = "# Todo\n- [ ] first";
+        assert_eq!(set_task_checked(content, 1, true), None);
+        assert_eq!(set_task_checked(content, 0, true), None);
+        assert_eq!(set_task_checked(content, 3, true), None);
+    }
+}
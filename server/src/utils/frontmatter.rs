@@ -0,0 +1,141 @@
+/// Markdown file with the article metadata in a front matter block
+pub struct Document {
+    /// Missing in files that aren't articles yet
+    pub id: Option<i32>,
+    pub title: String,
+    pub tags: Vec<String>,
+    pub content: String,
+}
+
+/// The title is quoted, so it can't end the front matter block
+pub fn render(id: i32, title: &str, tags: &[String], content: &str) -> String {
+    format!(
+        "---\nid: {}\ntitle: {}\ntags: [{}]\n---\n{}",
+        id,
+        quote(title),
+        tags.join(", "),
+        content
+    )
+}
+
+pub fn parse(file: &str) -> Option<Document> {
+    let rest = file.strip_prefix("---\n")?;
+    let (front_matter, content) = rest.split_once("\n---\n")?;
+    let (mut id, mut title, mut tags) = (None, None, vec![]);
+    for line in front_matter.lines() {
+        let (key, value) = line.split_once(':')?;
+        let value = value.trim();
+        match key.trim() {
+            "id" => id = Some(value.parse().ok()?),
+            "title" => title = Some(unquote(value)?),
+            "tags" => {
+                tags = value
+                    .trim_start_matches('[')
+                    .trim_end_matches(']')
+                    .split(',')
+                    .map(|t| t.trim().to_owned())
+                    .filter(|t| !t.is_empty())
+                    .collect()
+            }
+            _ => (),
+        }
+    }
+    Some(Document {
+        id,
+        title: title?,
+        tags,
+        content: content.to_owned(),
+    })
+}
+
+fn quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Reverses `quote`, values written without quotes are taken as is
+fn unquote(value: &str) -> Option<String> {
+    let inner = match value.strip_prefix('"') {
+        Some(rest) => rest.strip_suffix('"')?,
+        None => return Some(value.to_owned()),
+    };
+    let mut unquoted = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unquoted.push(match chars.next()? {
+                'n' => '\n',
+                'r' => '\r',
+                c @ ('"' | '\\') => c,
+                _ => return None,
+            }),
+            '"' => return None,
+            c => unquoted.push(c),
+        }
+    }
+    Some(unquoted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rendered_document_is_parsed() {
+        let tags = vec!["rust".to_owned(), "lang/go".to_owned()];
+        let file = render(3, "Notes: day one", &tags, "# Hello\n\n---\nworld\n");
+        let doc = parse(&file).unwrap();
+        assert_eq!(doc.id, Some(3));
+        assert_eq!(doc.title, "Notes: day one");
+        assert_eq!(doc.tags, tags);
+        assert_eq!(doc.content, "# Hello\n\n---\nworld\n");
+    }
+
+    #[test]
+    fn titles_are_quoted() {
+        for title in ["Line one\n---\nLine two", "--- leading dashes", "Say \"hi\" \\ bye\r"] {
+            let file = render(1, title, &[], "content\n");
+            assert_eq!(file.lines().filter(|l| *l == "---").count(), 2);
+            let doc = parse(&file).unwrap();
+            assert_eq!(doc.title, title);
+            assert_eq!(doc.content, "content\n");
+        }
+    }
+
+    #[test]
+    fn unquoted_titles_and_missing_ids_are_accepted() {
+        let doc = parse("---\ntitle: Written by hand\n---\ntext").unwrap();
+        assert_eq!(doc.id, None);
+        assert_eq!(doc.title, "Written by hand");
+        assert!(parse("---\ntitle: \"open\n---\n").is_none());
+        assert!(parse("---\ntitle: \"bad \\x\"\n---\n").is_none());
+    }
+
+    #[test]
+    fn empty_tags_are_skipped() {
+        let doc = parse("---\nid: 1\ntitle: A\ntags: [a, , b]\n---\n").unwrap();
+        assert_eq!(doc.tags, vec!["a", "b"]);
+        let doc = parse("---\nid: 1\ntitle: A\ntags: []\n---\n").unwrap();
+        assert!(doc.tags.is_empty());
+    }
+
+    #[test]
+    fn malformed_documents_are_rejected() {
+        assert!(parse("id: 1\ntitle: A\n").is_none());
+        assert!(parse("---\nid: 1\ntitle: A\n").is_none());
+        assert!(parse("---\nid: x\ntitle: A\n---\n").is_none());
+        assert!(parse("---\nid: 1\n---\n").is_none());
+        assert!(parse("---\nid: 1\nno separator\ntitle: A\n---\n").is_none());
+    }
+}
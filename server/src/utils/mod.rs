@@ -1,10 +1,142 @@
+use once_cell::sync::OnceCell;
+use rocket::serde::Deserialize;
+
+pub mod diff;
 pub mod extractor;
+pub mod frontmatter;
+
+// ========================== TEMPLATES ===================
+
+/// Replaces `{{key}}` placeholders with their values
+pub fn fill_placeholders(text: &str, values: &[(&str, &str)]) -> String {
+    values
+        .iter()
+        .fold(text.to_owned(), |text, (key, value)| {
+            text.replace(&format!("{{{{{}}}}}", key), value)
+        })
+}
+
+// ========================== LANGUAGE ====================
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct LanguageConfig {
+    pub fallback: String,
+    pub min_confidence: f64,
+}
+
+impl Default for LanguageConfig {
+    fn default() -> Self {
+        LanguageConfig {
+            fallback: "english".to_owned(),
+            min_confidence: 0.0,
+        }
+    }
+}
+
+struct LanguageSettings {
+    fallback: &'static str,
+    min_confidence: f64,
+    /// Text search configs of the database, older postgres versions lack some
+    installed: Vec<String>,
+}
+
+static LANGUAGE_SETTINGS: OnceCell<LanguageSettings> = OnceCell::new();
+
+// whatlang languages that have a matching postgres text search config
+const LANGUAGES: &[(whatlang::Lang, &str)] = &[
+    (whatlang::Lang::Ara, "arabic"),
+    (whatlang::Lang::Hye, "armenian"),
+    (whatlang::Lang::Cat, "catalan"),
+    (whatlang::Lang::Dan, "danish"),
+    (whatlang::Lang::Nld, "dutch"),
+    (whatlang::Lang::Eng, "english"),
+    (whatlang::Lang::Fin, "finnish"),
+    (whatlang::Lang::Fra, "french"),
+    (whatlang::Lang::Deu, "german"),
+    (whatlang::Lang::Ell, "greek"),
+    (whatlang::Lang::Hin, "hindi"),
+    (whatlang::Lang::Hun, "hungarian"),
+    (whatlang::Lang::Ind, "indonesian"),
+    (whatlang::Lang::Ita, "italian"),
+    (whatlang::Lang::Lit, "lithuanian"),
+    (whatlang::Lang::Nep, "nepali"),
+    (whatlang::Lang::Nob, "norwegian"),
+    (whatlang::Lang::Por, "portuguese"),
+    (whatlang::Lang::Ron, "romanian"),
+    (whatlang::Lang::Rus, "russian"),
+    (whatlang::Lang::Srp, "serbian"),
+    (whatlang::Lang::Spa, "spanish"),
+    (whatlang::Lang::Swe, "swedish"),
+    (whatlang::Lang::Tam, "tamil"),
+    (whatlang::Lang::Tur, "turkish"),
+    (whatlang::Lang::Yid, "yiddish"),
+];
+
+pub fn init_language(config: LanguageConfig, installed: Vec<String>) -> Result<(), String> {
+    let fallback = supported_config(&config.fallback)
+        .ok_or_else(|| format!("Unsupported fallback language {}", config.fallback))?;
+    let missing: Vec<&str> = LANGUAGES
+        .iter()
+        .map(|(_, config)| *config)
+        .filter(|config| !installed.iter().any(|c| c == config))
+        .collect();
+    if !missing.is_empty() {
+        log::warn!(
+            "Missing text search configs {}, using simple instead",
+            missing.join(", ")
+        );
+    }
+    let fallback = if missing.contains(&fallback) {
+        "simple"
+    } else {
+        fallback
+    };
+    LANGUAGE_SETTINGS
+        .set(LanguageSettings {
+            fallback,
+            min_confidence: config.min_confidence,
+            installed,
+        })
+        .map_err(|_| "Language settings already initialized".to_owned())
+}
+
+/// Returns the postgres text search config with the given name, if it is supported.
+/// Languages without a config in the database map to `simple`.
+pub fn supported_language(name: &str) -> Option<&'static str> {
+    supported_config(name).map(installed)
+}
 
 pub fn detect_language(content: &str) -> &'static str {
-    match whatlang::detect_lang(&content) {
-        Some(whatlang::Lang::Eng) => "english",
-        Some(whatlang::Lang::Deu) => "german",
-        Some(whatlang::Lang::Rus) => "russian",
-        _ => "english",
+    let (fallback, min_confidence) = match LANGUAGE_SETTINGS.get() {
+        Some(settings) => (settings.fallback, settings.min_confidence),
+        None => ("english", 0.0),
+    };
+    match whatlang::detect(content) {
+        Some(info) if info.confidence() >= min_confidence => LANGUAGES
+            .iter()
+            .find(|(lang, _)| *lang == info.lang())
+            .map(|(_, config)| installed(config))
+            .unwrap_or(fallback),
+        _ => fallback,
+    }
+}
+
+fn supported_config(name: &str) -> Option<&'static str> {
+    let name = name.to_lowercase();
+    if name == "simple" {
+        return Some("simple");
+    }
+    LANGUAGES
+        .iter()
+        .map(|(_, config)| *config)
+        .find(|config| *config == name)
+}
+
+/// The config if the database has it, `simple` otherwise
+fn installed(config: &'static str) -> &'static str {
+    match LANGUAGE_SETTINGS.get() {
+        Some(settings) if !settings.installed.iter().any(|c| c == config) => "simple",
+        _ => config,
     }
 }
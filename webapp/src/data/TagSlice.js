@@ -2,9 +2,14 @@ import { createSlice, createAsyncThunk } from '@reduxjs/toolkit'
 import { apiFetch } from './utils';
 
 const initialState = {
+    tree: [],
     list: []
 }
 
+// depth-first list of the tag tree
+const flatten = (tags, depth = 0) =>
+    tags.flatMap(t => [{...t, depth}, ...flatten(t.children, depth + 1)]);
+
 export const reloadTags = createAsyncThunk('tag/reload', async () => {
     const response = await apiFetch("/tags");
     if (response.status === 200) {
@@ -22,7 +27,8 @@ export const tagSlice = createSlice({
     },
     extraReducers: {
         [reloadTags.fulfilled]: (state, action) => {
-            state.list = action.payload;
+            state.tree = action.payload;
+            state.list = flatten(action.payload);
         }
     }
 })
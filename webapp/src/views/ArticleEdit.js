@@ -63,10 +63,11 @@ class DelayedTextField extends React.Component {
         }
     }
     render() {
-        const {label, minRows, multiline} = this.props;
+        const {label, minRows, multiline, error} = this.props;
         const {content} = this.state;
         return <TextField fullWidth variant="outlined" label={label}
             minRows={minRows} multiline={multiline}
+            error={!!error} helperText={error}
             value={content} onChange={(e) => {
                 this.setState({content: e.target.value});
                 this.delayedUpdate();
@@ -82,6 +83,7 @@ class ArticlEdit extends React.Component {
             tags: []
         },
         dirty: false,
+        errors: {},
         loading: true,
         tabIndex: 0
     }
@@ -105,7 +107,10 @@ class ArticlEdit extends React.Component {
                     if (typeof action.payload === "number") {
                         this.state.article.id = action.payload;
                     }
-                    this.setState({...this.state, dirty: false});
+                    // field errors by field name
+                    const invalid = _.get(action.payload, "kind.Invalid", []);
+                    const errors = _.fromPairs(invalid.map(e => [e.field, e.message]));
+                    this.setState({...this.state, dirty: invalid.length > 0, errors});
                 });
         }, 2000);
     }
@@ -157,8 +162,9 @@ class ArticlEdit extends React.Component {
     }
     
     renderEditPanel() {
-        const {article} = this.state;
+        const {article, errors} = this.state;
         const {tags} = this.props;
+        const tagErrors = _.uniq(_.filter(errors, (_message, field) => field.startsWith("tags")));
         const setLiveContent = (liveContent) => {
             this.setArticleField("content", liveContent);
             this.saveArticle();
@@ -173,10 +179,10 @@ class ArticlEdit extends React.Component {
         }
         return <Box>
                 <TextField variant="outlined" label="title"
-                    fullWidth 
+                    fullWidth error={!!errors.title} helperText={errors.title}
                     value={article.title} onChange={(e) => setLiveTitile(e.target.value)}/>
                 <DelayedTextField multiline label="content"
-                    minRows={10} content={article.content}
+                    minRows={10} content={article.content} error={errors.content}
                     update={(c) => setLiveContent(c)} />
                 <Autocomplete
                     multiple freeSolo
@@ -189,6 +195,8 @@ class ArticlEdit extends React.Component {
                           {...params}
                           variant="standard"
                           label="Tags"
+                          error={tagErrors.length > 0}
+                          helperText={tagErrors.join(", ")}
                         />
                       )}
                 />
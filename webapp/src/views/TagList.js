@@ -13,7 +13,7 @@ function TagList() {
 
     return <Container size="md">
         <List>
-            {tags.map((t) => <ListItem>
+            {tags.map((t) => <ListItem style={{paddingLeft: 16 + t.depth * 24}}>
                 <ListItemAvatar>
                     <Avatar> {t.num_articles} </Avatar>
                 </ListItemAvatar>